@@ -22,12 +22,12 @@ impl Default for AABB {
 
 impl AABB {
     pub fn new(a: Interval, b: Interval, c: Interval) -> Self {
-        let val = Self { x: a, y: b, z: c };
+        let mut val = Self { x: a, y: b, z: c };
         val.pad_to_minimums();
         val
     }
     pub fn point_new(a: &Vector, b: &Vector) -> Self {
-        let val = Self {
+        let mut val = Self {
             x: if a.x <= b.x {
                 Interval::new(a.x, b.x)
             } else {
@@ -63,16 +63,18 @@ impl AABB {
             self.x
         }
     }
-    fn pad_to_minimums(&self) {
+    // Flat faces, such as axis-aligned quads and triangles, would get boxes of
+    // zero thickness that no ray can enter, so their BVH nodes would be culled.
+    fn pad_to_minimums(&mut self) {
         let delta = 0.0001;
         if self.x.size() < delta {
-            self.x.expand(delta);
+            self.x = self.x.expand(delta);
         }
         if self.y.size() < delta {
-            self.y.expand(delta);
+            self.y = self.y.expand(delta);
         }
         if self.z.size() < delta {
-            self.z.expand(delta);
+            self.z = self.z.expand(delta);
         }
     }
     pub fn hit(&self, r: &Ray, ray_t: &Interval) -> bool {
@@ -105,6 +107,22 @@ impl AABB {
         }
         true
     }
+    // The part of `ray_t` during which the ray is inside the box, if any.
+    pub fn hit_interval(&self, r: &Ray, ray_t: &Interval) -> Option<Interval> {
+        let mut inside = *ray_t;
+        for axis in 0..3 {
+            let ax = self.axis_interval(axis);
+            let adinv = 1.0 / r.direction.at(axis);
+            let t0 = (ax.min - r.origin.at(axis)) * adinv;
+            let t1 = (ax.max - r.origin.at(axis)) * adinv;
+            inside.min = inside.min.max(t0.min(t1));
+            inside.max = inside.max.min(t0.max(t1));
+            if inside.max <= inside.min {
+                return None;
+            }
+        }
+        Some(inside)
+    }
     pub fn longest_axis(&self) -> u32 {
         if self.x.size() > self.y.size() {
             if self.x.size() > self.z.size() {
@@ -130,3 +148,20 @@ impl Add<Vector> for AABB {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_boxes_are_padded_and_hit() {
+        let flat = AABB::point_new(&Vector::new(0.0, 0.0, 1.0), &Vector::new(1.0, 1.0, 1.0));
+        assert!(flat.z.size() >= 0.0001);
+        assert!(flat.z.contains(1.0));
+        let r = Ray::new(Vector::new(0.5, 0.5, 3.0), Vector::new(0.0, 0.0, -1.0), 0.0);
+        assert!(flat.hit(&r, &Interval::new(0.0, f64::INFINITY)));
+        assert!(flat
+            .hit_interval(&r, &Interval::new(0.0, f64::INFINITY))
+            .is_some());
+    }
+}
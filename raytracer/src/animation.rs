@@ -0,0 +1,227 @@
+use std::sync::Arc;
+
+use crate::camera::Camera;
+use crate::hittable::Hittable;
+use crate::hittable_list::HittableList;
+use crate::vec3::Vector;
+
+#[derive(Copy, Clone)]
+pub struct CameraKeyframe {
+    pub time: f64,
+    pub lookfrom: Vector,
+    pub lookat: Vector,
+    pub vfov: f64,
+    pub focus_dist: f64,
+}
+
+impl CameraKeyframe {
+    pub fn new(time: f64, lookfrom: Vector, lookat: Vector, vfov: f64, focus_dist: f64) -> Self {
+        Self {
+            time,
+            lookfrom,
+            lookat,
+            vfov,
+            focus_dist,
+        }
+    }
+}
+
+// Keyframe times and frame shutter windows share the ray time range [0, 1], so an
+// object built with `Sphere::new_moving` travels across the whole sequence and each
+// frame only blurs over its own slice of that path.
+pub struct CameraAnimation {
+    pub keyframes: Vec<CameraKeyframe>,
+    pub frame_count: u32,
+    pub shutter: f64,
+}
+
+impl CameraAnimation {
+    pub fn new(frame_count: u32, shutter: f64) -> Self {
+        Self {
+            keyframes: Vec::new(),
+            frame_count,
+            shutter: shutter.clamp(0.0, 1.0),
+        }
+    }
+    pub fn add_keyframe(&mut self, key: CameraKeyframe) {
+        let index = self
+            .keyframes
+            .iter()
+            .position(|k| k.time > key.time)
+            .unwrap_or(self.keyframes.len());
+        self.keyframes.insert(index, key);
+    }
+    pub fn shutter_window(&self, frame: u32) -> (f64, f64) {
+        let frame_length = 1.0 / (self.frame_count.max(1) as f64);
+        let open = frame as f64 * frame_length;
+        (open, open + self.shutter * frame_length)
+    }
+    // The interpolated camera at `time`, or None before any keyframe is added.
+    pub fn sample(&self, time: f64) -> Option<CameraKeyframe> {
+        let n = self.keyframes.len();
+        if n == 0 {
+            return None;
+        }
+        if n == 1 || time <= self.keyframes[0].time {
+            return Some(self.keyframes[0]);
+        }
+        if time >= self.keyframes[n - 1].time {
+            return Some(self.keyframes[n - 1]);
+        }
+        let k = self
+            .keyframes
+            .iter()
+            .rposition(|key| key.time <= time)
+            .unwrap()
+            .min(n - 2);
+        let k0 = &self.keyframes[k.saturating_sub(1)];
+        let k1 = &self.keyframes[k];
+        let k2 = &self.keyframes[k + 1];
+        let k3 = &self.keyframes[(k + 2).min(n - 1)];
+        let s = (time - k1.time) / (k2.time - k1.time);
+        Some(CameraKeyframe {
+            time,
+            lookfrom: catmull_rom_vector(k0.lookfrom, k1.lookfrom, k2.lookfrom, k3.lookfrom, s),
+            lookat: catmull_rom_vector(k0.lookat, k1.lookat, k2.lookat, k3.lookat, s),
+            vfov: catmull_rom(k0.vfov, k1.vfov, k2.vfov, k3.vfov, s),
+            focus_dist: catmull_rom(
+                k0.focus_dist,
+                k1.focus_dist,
+                k2.focus_dist,
+                k3.focus_dist,
+                s,
+            ),
+        })
+    }
+    // The camera is placed where it is halfway through the shutter window and
+    // held there, so only the objects blur within a frame, not the camera's own
+    // motion. Without keyframes `cam` is used as it is.
+    pub fn frame_camera(&self, cam: &Camera, frame: u32) -> Camera {
+        let (open, close) = self.shutter_window(frame);
+        let mut frame_cam = cam.clone();
+        if let Some(key) = self.sample((open + close) / 2.0) {
+            frame_cam.lookfrom = key.lookfrom;
+            frame_cam.lookat = key.lookat;
+            frame_cam.vfov = key.vfov;
+            frame_cam.focus_dist = key.focus_dist;
+        }
+        frame_cam.shutter_open = open;
+        frame_cam.shutter_close = close;
+        frame_cam
+    }
+    pub fn render(
+        &self,
+        cam: &Camera,
+        world: HittableList,
+        lights: Arc<dyn Hittable>,
+        directory: &str,
+    ) {
+        for frame in 0..self.frame_count {
+            println!("Rendering frame {}/{}", frame + 1, self.frame_count);
+            let mut frame_cam = self.frame_camera(cam, frame);
+            let filename = format!("{}/frame_{:04}.jpg", directory, frame);
+            frame_cam.render_to(world.clone(), lights.clone(), &filename);
+        }
+    }
+}
+
+fn catmull_rom(p0: f64, p1: f64, p2: f64, p3: f64, t: f64) -> f64 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * (2.0 * p1
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+fn catmull_rom_vector(p0: Vector, p1: Vector, p2: Vector, p3: Vector, t: f64) -> Vector {
+    Vector::new(
+        catmull_rom(p0.x, p1.x, p2.x, p3.x, t),
+        catmull_rom(p0.y, p1.y, p2.y, p3.y, t),
+        catmull_rom(p0.z, p1.z, p2.z, p3.z, t),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn near(a: Vector, b: Vector) -> bool {
+        (a - b).near_zero()
+    }
+
+    // Four keyframes evenly spaced in time and along the x axis.
+    fn straight_line() -> CameraAnimation {
+        let mut animation = CameraAnimation::new(6, 0.5);
+        for k in [3, 0, 2, 1] {
+            let x = k as f64;
+            animation.add_keyframe(CameraKeyframe::new(
+                x / 3.0,
+                Vector::new(x, 0.0, 0.0),
+                Vector::new(x, 0.0, -1.0),
+                40.0 + 10.0 * x,
+                1.0,
+            ));
+        }
+        animation
+    }
+
+    #[test]
+    fn sample_passes_through_the_keyframes() {
+        let animation = straight_line();
+        assert!(animation
+            .keyframes
+            .windows(2)
+            .all(|pair| pair[0].time < pair[1].time));
+        for key in animation.keyframes.iter() {
+            let sampled = animation.sample(key.time).unwrap();
+            assert!(near(sampled.lookfrom, key.lookfrom));
+            assert!((sampled.vfov - key.vfov).abs() < 1e-12);
+        }
+        // Catmull-Rom reproduces even motion between the inner keyframes.
+        let middle = animation.sample(0.5).unwrap();
+        assert!(near(middle.lookfrom, Vector::new(1.5, 0.0, 0.0)));
+        assert!((middle.vfov - 55.0).abs() < 1e-12);
+        // Outside the keyframes the camera holds still.
+        assert!(near(
+            animation.sample(-1.0).unwrap().lookfrom,
+            Vector::new(0.0, 0.0, 0.0)
+        ));
+        assert!(near(
+            animation.sample(2.0).unwrap().lookfrom,
+            Vector::new(3.0, 0.0, 0.0)
+        ));
+        assert!(CameraAnimation::new(6, 0.5).sample(0.5).is_none());
+    }
+
+    #[test]
+    fn shutter_windows_do_not_overlap() {
+        for shutter in [0.0, 0.5, 1.0, 2.0] {
+            let animation = CameraAnimation::new(5, shutter);
+            let windows: Vec<(f64, f64)> = (0..5).map(|f| animation.shutter_window(f)).collect();
+            for (open, close) in windows.iter() {
+                assert!(0.0 <= *open && open <= close && *close <= 1.0 + 1e-12);
+            }
+            for pair in windows.windows(2) {
+                assert!(pair[0].1 <= pair[1].0 + 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn frame_camera_is_placed_at_the_middle_of_its_window() {
+        let animation = straight_line();
+        let cam: Camera = Default::default();
+        let frame_cam = animation.frame_camera(&cam, 2);
+        let (open, close) = animation.shutter_window(2);
+        assert_eq!(
+            (frame_cam.shutter_open, frame_cam.shutter_close),
+            (open, close)
+        );
+        let key = animation.sample((open + close) / 2.0).unwrap();
+        assert!(near(frame_cam.lookfrom, key.lookfrom));
+
+        let still = CameraAnimation::new(6, 0.5).frame_camera(&cam, 2);
+        assert!(near(still.lookfrom, cam.lookfrom));
+    }
+}
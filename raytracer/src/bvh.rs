@@ -19,44 +19,61 @@ impl BvhNode {
         let len = list.size();
         Self::new(&mut list.objects, 0, len)
     }
-    pub fn new(objects: &mut Vec<Arc<dyn Hittable>>, start: usize, end: usize) -> Self {
+    pub fn new(objects: &mut [Arc<dyn Hittable>], start: usize, end: usize) -> Self {
+        let mut boxed: Vec<(AABB, Arc<dyn Hittable>)> = objects[start..end]
+            .iter()
+            .map(|object| (object.bounding_box(), object.clone()))
+            .collect();
+        Self::build(&mut boxed)
+    }
+    // Bounding boxes are computed once up front; objects like mesh faces derive
+    // theirs on demand and would otherwise be re-evaluated by every comparison.
+    fn build(objects: &mut [(AABB, Arc<dyn Hittable>)]) -> Self {
         let mut bbox = AABB::default();
-        for object in objects.iter().take(end).skip(start) {
-            bbox = AABB::box_new(&bbox, &object.bounding_box());
+        for (object_box, _) in objects.iter() {
+            bbox = AABB::box_new(&bbox, object_box);
         }
         let axis = bbox.longest_axis();
-        let object_span: usize = end - start;
-        if object_span == 1 {
-            let left = &objects[start];
-            let right = &objects[start];
+        let object_span: usize = objects.len();
+        if object_span == 0 {
+            // Importers can produce empty meshes; their node is never hit.
+            let empty = Arc::new(HittableList::new());
+            Self {
+                left: empty.clone(),
+                right: empty,
+                bbox,
+            }
+        } else if object_span == 1 {
             Self {
-                left: left.clone(),
-                right: right.clone(),
-                bbox: AABB::box_new(&left.bounding_box(), &right.bounding_box()),
+                left: objects[0].1.clone(),
+                right: objects[0].1.clone(),
+                bbox: objects[0].0,
             }
         } else if object_span == 2 {
-            let left = &objects[start];
-            let right = &objects[start + 1];
             Self {
-                left: left.clone(),
-                right: right.clone(),
-                bbox: AABB::box_new(&left.bounding_box(), &right.bounding_box()),
+                left: objects[0].1.clone(),
+                right: objects[1].1.clone(),
+                bbox,
             }
         } else {
-            objects[start..end].sort_by(|a, b| Self::box_compare(a, b, axis));
-            let mid = start + object_span / 2;
-            let left = Self::new(objects, start, mid);
-            let right = Self::new(objects, mid, end);
+            objects.sort_by(|a, b| Self::axis_compare(&a.0, &b.0, axis));
+            let mid = object_span / 2;
+            let (left_objects, right_objects) = objects.split_at_mut(mid);
+            let left = Self::build(left_objects);
+            let right = Self::build(right_objects);
             Self {
-                left: Arc::new(left.clone()),
-                right: Arc::new(right.clone()),
-                bbox: AABB::box_new(&left.bounding_box(), &right.bounding_box()),
+                left: Arc::new(left),
+                right: Arc::new(right),
+                bbox,
             }
         }
     }
     pub fn box_compare(a: &Arc<dyn Hittable>, b: &Arc<dyn Hittable>, axis_index: u32) -> Ordering {
-        let a_axis_interval = a.bounding_box().axis_interval(axis_index);
-        let b_axis_interval = b.bounding_box().axis_interval(axis_index);
+        Self::axis_compare(&a.bounding_box(), &b.bounding_box(), axis_index)
+    }
+    fn axis_compare(a: &AABB, b: &AABB, axis_index: u32) -> Ordering {
+        let a_axis_interval = a.axis_interval(axis_index);
+        let b_axis_interval = b.axis_interval(axis_index);
         a_axis_interval
             .min
             .partial_cmp(&b_axis_interval.min)
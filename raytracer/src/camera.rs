@@ -11,10 +11,12 @@ use crate::hittable::Hittable;
 use crate::hittable_list::HittableList;
 use crate::interval::Interval;
 use crate::material::ScatterRecord;
+use crate::matrix::Matrix4;
 // use crate::pdf::CosinePdf;
 use crate::pdf::{HittablePdf, MixturePdf, Pdf};
 use crate::ray::Ray;
 use crate::rtweekend::random_double;
+use crate::rtweekend::random_int_range;
 use crate::rtweekend::INFINITY;
 use crate::rtweekend::{self, degrees_to_radians};
 // use crate::rtweekend::PI;
@@ -47,6 +49,14 @@ pub struct Camera {
     pub background: Vector,
     pub sqrt_spp: i32,
     pub recip_sqrt_spp: f64,
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+    pub lens_shift: f64,
+    pub distortion_k1: f64,
+    pub distortion_k2: f64,
+    pub vignetting: bool,
+    pub chromatic_aberration: f64,
+    pub roll: f64,
 }
 
 impl Default for Camera {
@@ -76,17 +86,104 @@ impl Default for Camera {
             background: Vector::new(0.0, 0.0, 0.0),
             sqrt_spp: 0,
             recip_sqrt_spp: 0.0,
+            shutter_open: 0.0,
+            shutter_close: 1.0,
+            lens_shift: 0.0,
+            distortion_k1: 0.0,
+            distortion_k2: 0.0,
+            vignetting: false,
+            chromatic_aberration: 0.0,
+            roll: 0.0,
         }
     }
 }
 
 impl Camera {
+    // Plain settings for the placed constructors below; `Default` is tuned for
+    // `final_scene` and would give them its image size, samples and focus.
+    fn placed(lookfrom: Vector, lookat: Vector, vup: Vector) -> Self {
+        Self {
+            aspect_ratio: 1.0,
+            image_width: 400,
+            image_height: 0,
+            samples_per_pixel: 100,
+            pixel_samples_scale: 0.0,
+            center: Vector::new(0.0, 0.0, 0.0),
+            pixel00_loc: Vector::new(0.0, 0.0, 0.0),
+            pixel_delta_u: Vector::new(0.0, 0.0, 0.0),
+            pixel_delta_v: Vector::new(0.0, 0.0, 0.0),
+            max_depth: 50,
+            vfov: 90.0,
+            lookfrom,
+            lookat,
+            vup,
+            u: Vector::new(0.0, 0.0, 0.0),
+            v: Vector::new(0.0, 0.0, 0.0),
+            w: Vector::new(0.0, 0.0, 0.0),
+            defocus_angle: 0.0,
+            focus_dist: (lookat - lookfrom).length(),
+            defocus_disk_u: Vector::new(0.0, 0.0, 0.0),
+            defocus_disk_v: Vector::new(0.0, 0.0, 0.0),
+            background: Vector::new(0.0, 0.0, 0.0),
+            sqrt_spp: 0,
+            recip_sqrt_spp: 0.0,
+            shutter_open: 0.0,
+            shutter_close: 1.0,
+            lens_shift: 0.0,
+            distortion_k1: 0.0,
+            distortion_k2: 0.0,
+            vignetting: false,
+            chromatic_aberration: 0.0,
+            roll: 0.0,
+        }
+    }
+    // A camera looking straight up or down has no horizon, so the top of the
+    // image then faces -z instead of the world up axis.
+    pub fn direction_new(position: Vector, direction: Vector) -> Self {
+        let mut val = Self::placed(position, position + direction, Vector::new(0.0, 1.0, 0.0));
+        if val.vup.cross(&direction.unit()).near_zero() {
+            val.vup = Vector::new(0.0, 0.0, -1.0);
+        }
+        val
+    }
+    // Yaw turns from +z towards +x around the world up axis, pitch tilts the view
+    // up and roll spins the image clockwise around the view direction, all in degrees.
+    pub fn angle_new(position: Vector, yaw: f64, pitch: f64, roll: f64) -> Self {
+        let yaw = degrees_to_radians(yaw);
+        let pitch = degrees_to_radians(pitch);
+        let direction = Vector::new(
+            yaw.sin() * pitch.cos(),
+            pitch.sin(),
+            yaw.cos() * pitch.cos(),
+        );
+        // The image's up direction tilted along with the view, so it stays
+        // defined at pitch = ±90°.
+        let vup = Vector::new(
+            -yaw.sin() * pitch.sin(),
+            pitch.cos(),
+            -yaw.cos() * pitch.sin(),
+        );
+        Self {
+            roll,
+            vup,
+            ..Self::direction_new(position, direction)
+        }
+    }
+    // Camera-to-world matrix: the columns are right, up, backward and position,
+    // so the camera looks down its local -z axis.
+    pub fn matrix_new(m: &Matrix4) -> Self {
+        let position = m.column(3);
+        Self::placed(position, position - m.column(2), m.column(1))
+    }
     pub fn render(&mut self, world: HittableList, lights: Arc<dyn Hittable>) {
-        let path = std::path::Path::new("output/test/image5.jpg");
-        let prefix = path.parent().unwrap();
-        std::fs::create_dir_all(prefix).expect("Cannot create all the parents");
+        self.render_to(world, lights, "output/test/image5.jpg");
+    }
+    pub fn render_to(&mut self, world: HittableList, lights: Arc<dyn Hittable>, filename: &str) {
+        let img = self.render_image(&world, lights);
+        Self::save_image(img, filename);
+    }
+    pub fn render_image(&mut self, world: &HittableList, lights: Arc<dyn Hittable>) -> RgbImage {
         self.initialise();
-        let quality = 100;
         let img: RgbImage = ImageBuffer::new(self.image_width, self.image_height);
 
         let progress = if option_env!("CI").unwrap_or_default() == "true" {
@@ -112,9 +209,10 @@ impl Camera {
                     // }
                     for s_j in 0..copy.sqrt_spp {
                         for s_i in 0..copy.sqrt_spp {
-                            let r = copy.get_ray(i, j, s_i, s_j);
+                            let (r, weight) = copy.get_ray(i, j, s_i, s_j);
                             pixel_color = pixel_color
-                                + copy.ray_color(&r, copy.max_depth, &world, lights.clone())
+                                + weight
+                                    * copy.ray_color(&r, copy.max_depth, &world, lights.clone())
                         }
                     }
                     pixel_color = pixel_color * copy.pixel_samples_scale;
@@ -134,8 +232,15 @@ impl Camera {
         }
         progress.lock().unwrap().finish();
         let img = Some(Arc::try_unwrap(img).unwrap().into_inner().unwrap());
-        let img = img.as_ref().unwrap().clone();
         // let img = Self::edge_detection(&mut img, 100, 150, 1);
+        img.unwrap()
+    }
+    pub fn save_image(img: RgbImage, filename: &str) {
+        let path = std::path::Path::new(filename);
+        if let Some(prefix) = path.parent() {
+            std::fs::create_dir_all(prefix).expect("Cannot create all the parents");
+        }
+        let quality = 100;
         println!(
             "Ouput image as \"{}\"",
             style(path.to_str().unwrap()).yellow()
@@ -147,7 +252,7 @@ impl Camera {
             Err(_) => println!("{}", style("Outputting image fails.").red()),
         }
     }
-    fn initialise(&mut self) {
+    pub fn initialise(&mut self) {
         self.image_height = (self.image_width as f64 / self.aspect_ratio).round() as u32;
         self.image_height = if self.image_height < 1 {
             1
@@ -168,13 +273,22 @@ impl Camera {
         self.w = (self.lookfrom - self.lookat).unit();
         self.u = self.vup.cross(&self.w).unit();
         self.v = self.w.cross(&self.u);
+        if self.roll != 0.0 {
+            let roll = degrees_to_radians(self.roll);
+            let (sin_roll, cos_roll) = roll.sin_cos();
+            let u = self.u * cos_roll + self.v * sin_roll;
+            self.v = self.v * cos_roll - self.u * sin_roll;
+            self.u = u;
+        }
         let viewport_u: Vector = self.u * viewport_width;
         let viewport_v: Vector = self.v * (-viewport_height);
         self.pixel_delta_u = viewport_u / (self.image_width as f64);
         self.pixel_delta_v = viewport_v / (self.image_height as f64);
         let viewport_upper_left =
             self.center - self.w * self.focus_dist - viewport_u / 2.0 - viewport_v / 2.0;
-        self.pixel00_loc = viewport_upper_left + (self.pixel_delta_u + self.pixel_delta_v) * 0.5;
+        self.pixel00_loc = viewport_upper_left
+            + (self.pixel_delta_u + self.pixel_delta_v) * 0.5
+            + self.u * self.lens_shift;
         let defocus_radius = self.focus_dist * (degrees_to_radians(self.defocus_angle / 2.0)).tan();
         self.defocus_disk_u = self.u * defocus_radius;
         self.defocus_disk_v = self.v * defocus_radius;
@@ -182,19 +296,56 @@ impl Camera {
     // fn sample_square() -> Vector {
     //     Vector::new(random_double() - 0.5, random_double() - 0.5, 0.0)
     // }
-    fn get_ray(&self, i: u32, j: u32, s_i: i32, s_j: i32) -> Ray {
+    fn get_ray(&self, i: u32, j: u32, s_i: i32, s_j: i32) -> (Ray, Vector) {
         let offset: Vector = self.sample_square_stratified(s_i, s_j);
         let pixel_sample: Vector = self.pixel00_loc
             + self.pixel_delta_u * (i as f64 + offset.x)
             + self.pixel_delta_v * (j as f64 + offset.y);
+        let (pixel_sample, weight) = self.apply_lens_effects(pixel_sample);
         let ray_origin: Vector = if self.defocus_angle <= 0.0 {
             self.center
         } else {
             self.defocus_disk_sample()
         };
         let ray_direction = pixel_sample - ray_origin;
-        let ray_time = random_double();
-        Ray::new(ray_origin, ray_direction, ray_time)
+        let ray_time =
+            self.shutter_open + random_double() * (self.shutter_close - self.shutter_open);
+        (Ray::new(ray_origin, ray_direction, ray_time), weight)
+    }
+    // Lens effects move the sample on the focus plane before the ray is built,
+    // so defocus blur still converges on the distorted image.
+    // Positive k1/k2 give barrel distortion, negative values pincushion.
+    fn apply_lens_effects(&self, pixel_sample: Vector) -> (Vector, Vector) {
+        let mut weight = Vector::new(1.0, 1.0, 1.0);
+        if self.distortion_k1 == 0.0
+            && self.distortion_k2 == 0.0
+            && self.chromatic_aberration == 0.0
+            && !self.vignetting
+        {
+            return (pixel_sample, weight);
+        }
+        let optical_center = self.center - self.w * self.focus_dist + self.u * self.lens_shift;
+        let mut offset = pixel_sample - optical_center;
+        let half_height = self.focus_dist * (degrees_to_radians(self.vfov) / 2.0).tan();
+        let r2 = offset.length_square() / (half_height * half_height);
+        let mut scale = 1.0 + self.distortion_k1 * r2 + self.distortion_k2 * r2 * r2;
+        if self.chromatic_aberration != 0.0 {
+            let channel = random_int_range(0, 2);
+            scale *= 1.0 + self.chromatic_aberration * (channel as f64 - 1.0);
+            weight = match channel {
+                0 => Vector::new(3.0, 0.0, 0.0),
+                1 => Vector::new(0.0, 3.0, 0.0),
+                _ => Vector::new(0.0, 0.0, 3.0),
+            };
+        }
+        offset = offset * scale;
+        if self.vignetting {
+            let cos_theta = self.focus_dist
+                / (self.focus_dist * self.focus_dist + offset.length_square()).sqrt();
+            let falloff = cos_theta * cos_theta * cos_theta * cos_theta;
+            weight = weight * falloff;
+        }
+        (optical_center + offset, weight)
     }
     fn sample_square_stratified(&self, s_i: i32, s_j: i32) -> Vector {
         let px = ((s_i as f64 + random_double()) * self.recip_sqrt_spp) - 0.5;
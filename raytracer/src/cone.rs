@@ -0,0 +1,199 @@
+use crate::aabb::AABB;
+use crate::disk::Disk;
+use crate::hittable::{area_pdf_value, HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::rtweekend::{random_double, solve_quadratic, PI};
+use crate::vec3::Vector;
+
+use std::sync::Arc;
+
+// A cone with a base disk of `radius` at `base` and its apex at `base + axis`,
+// optionally closed by the base disk. u runs around the axis and v to the apex.
+pub struct Cone {
+    pub base: Vector,
+    pub frame: Onb,
+    pub height: f64,
+    pub radius: f64,
+    pub caps: Vec<Disk>,
+    pub mat: Arc<dyn Material>,
+    pub bbox: AABB,
+    pub side_area: f64,
+    pub area: f64,
+}
+
+impl Cone {
+    pub fn new(
+        base: Vector,
+        axis: Vector,
+        radius: f64,
+        capped: bool,
+        mat: Arc<dyn Material>,
+    ) -> Self {
+        let mut frame: Onb = Default::default();
+        frame.build_from_w(axis);
+        let height = axis.length();
+        let caps = if capped {
+            vec![Disk::new(base, axis * -1.0, radius, mat.clone())]
+        } else {
+            Vec::new()
+        };
+        let side_area = PI * radius * (radius * radius + height * height).sqrt();
+        let apex = base + axis;
+        Self {
+            base,
+            frame,
+            height,
+            radius,
+            area: side_area + caps.iter().map(|cap| cap.area).sum::<f64>(),
+            caps,
+            mat,
+            bbox: AABB::box_new(
+                &Disk::circle_bounding_box(base, frame.axis[2], radius),
+                &AABB::point_new(&apex, &apex),
+            ),
+            side_area,
+        }
+    }
+    // Points on the side satisfy x^2 + y^2 = (k (h - z))^2 with k = radius / height;
+    // the nappe mirrored through the apex is cut away by 0 <= z <= h.
+    fn hit_side(&self, r: &Ray, ray_t: &Interval) -> Option<HitRecord> {
+        let o = self.frame.coords(r.origin - self.base);
+        let d = self.frame.coords(r.direction);
+        let k = self.radius / self.height;
+        let k2 = k * k;
+        let h = self.height - o.z;
+        let a = d.x * d.x + d.y * d.y - k2 * d.z * d.z;
+        let b = 2.0 * (o.x * d.x + o.y * d.y + k2 * h * d.z);
+        let c = o.x * o.x + o.y * o.y - k2 * h * h;
+        let (t0, t1) = solve_quadratic(a, b, c)?;
+        for t in [t0, t1] {
+            let z = o.z + t * d.z;
+            if !ray_t.surrounds(t) || z < 0.0 || z > self.height {
+                continue;
+            }
+            let x = o.x + t * d.x;
+            let y = o.y + t * d.y;
+            let gradient = self.frame.local(x, y, k2 * (self.height - z));
+            let outward_normal = if gradient.near_zero() {
+                self.frame.axis[2]
+            } else {
+                gradient.unit()
+            };
+            let mut rec = HitRecord::new(r.at(t), Vector::default(), t, false);
+            rec.set_face_normal(r, &outward_normal);
+            rec.u = (y.atan2(x) + PI) / (2.0 * PI);
+            rec.v = z / self.height;
+            // The radius shrinks linearly to nothing at the apex.
+            let phi = y.atan2(x);
+            rec.dpdu = self.frame.local(-y, x, 0.0) * (2.0 * PI);
+            rec.dpdv = self.frame.local(
+                -self.radius * phi.cos(),
+                -self.radius * phi.sin(),
+                self.height,
+            );
+            rec.mat = Some(self.mat.clone());
+            return Some(rec);
+        }
+        None
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(&self, r: &Ray, ray_t: &Interval) -> Option<HitRecord> {
+        let mut closest = self.hit_side(r, ray_t);
+        for cap in self.caps.iter() {
+            let t_max = closest.as_ref().map_or(ray_t.max, |rec| rec.t);
+            if let Some(rec) = cap.hit(r, &Interval::new(ray_t.min, t_max)) {
+                closest = Some(rec);
+            }
+        }
+        closest
+    }
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+    fn pdf_value(&self, origin: Vector, direction: Vector) -> f64 {
+        area_pdf_value(self, self.area, origin, direction)
+    }
+    // The side area up to distance f from the apex grows as f^2, so f = sqrt(xi)
+    // samples it uniformly.
+    fn random(&self, origin: Vector) -> Vector {
+        let mut pick = random_double() * self.area - self.side_area;
+        if pick >= 0.0 {
+            for cap in self.caps.iter() {
+                if pick < cap.area {
+                    return cap.random(origin);
+                }
+                pick -= cap.area;
+            }
+        }
+        let f = random_double().sqrt();
+        let phi = 2.0 * PI * random_double();
+        let p = self.frame.local(
+            self.radius * f * phi.cos(),
+            self.radius * f * phi.sin(),
+            self.height * (1.0 - f),
+        );
+        self.base + p - origin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::assert_pdf_matches_random;
+    use crate::material::Lambertian;
+    use crate::rtweekend::INFINITY;
+
+    // A base of radius 1 at the origin and the apex at y = 2.
+    fn cone(capped: bool) -> Cone {
+        let mat: Arc<dyn Material> = Arc::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5)));
+        Cone::new(
+            Vector::default(),
+            Vector::new(0.0, 2.0, 0.0),
+            1.0,
+            capped,
+            mat,
+        )
+    }
+
+    #[test]
+    fn side_normals_lean_towards_the_apex() {
+        let r = Ray::new(Vector::new(5.0, 1.0, 0.0), Vector::new(-1.0, 0.0, 0.0), 0.0);
+        let rec = cone(true).hit(&r, &Interval::new(0.001, INFINITY)).unwrap();
+        // Halfway up the radius is 0.5.
+        assert!((rec.t - 4.5).abs() < 1e-12);
+        assert!(rec.front_face);
+        let slant = Vector::new(2.0, 1.0, 0.0).unit();
+        assert!((rec.normal - slant).near_zero());
+        // Rays past the apex miss the mirrored nappe.
+        let r = Ray::new(Vector::new(5.0, 3.0, 0.0), Vector::new(-1.0, 0.0, 0.0), 0.0);
+        assert!(cone(true)
+            .hit(&r, &Interval::new(0.001, INFINITY))
+            .is_none());
+    }
+
+    #[test]
+    fn base_cap_closes_only_capped_cones() {
+        let r = Ray::new(Vector::new(0.2, -3.0, 0.1), Vector::new(0.0, 1.0, 0.0), 0.0);
+        let rec = cone(true).hit(&r, &Interval::new(0.001, INFINITY)).unwrap();
+        assert!((rec.t - 3.0).abs() < 1e-12);
+        assert!((rec.normal - Vector::new(0.0, -1.0, 0.0)).near_zero());
+        let rec = cone(false)
+            .hit(&r, &Interval::new(0.001, INFINITY))
+            .unwrap();
+        // Without the cap the ray meets the inside of the side, where the radius is 0.2236.
+        let y = 2.0 * (1.0 - 0.05f64.sqrt());
+        assert!((rec.t - 3.0 - y).abs() < 1e-12);
+        assert!(!rec.front_face);
+    }
+
+    #[test]
+    fn pdf_matches_random() {
+        assert_pdf_matches_random(&cone(true), Vector::new(2.0, 3.0, 1.5));
+        assert_pdf_matches_random(&cone(false), Vector::new(2.0, -1.0, 1.5));
+    }
+}
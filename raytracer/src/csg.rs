@@ -0,0 +1,202 @@
+use crate::aabb::AABB;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::rtweekend::INFINITY;
+
+use std::sync::Arc;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+impl CsgOperation {
+    fn contains(&self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOperation::Union => in_left || in_right,
+            CsgOperation::Intersection => in_left && in_right,
+            CsgOperation::Difference => in_left && !in_right,
+        }
+    }
+}
+
+// A boolean combination of two closed objects. Surfaces carved out of `left` by
+// a difference keep the material of `right`, like the walls of a drilled hole.
+pub struct Csg {
+    pub left: Arc<dyn Hittable>,
+    pub right: Arc<dyn Hittable>,
+    pub operation: CsgOperation,
+    pub bbox: AABB,
+}
+
+impl Csg {
+    pub fn new(operation: CsgOperation, left: Arc<dyn Hittable>, right: Arc<dyn Hittable>) -> Self {
+        let (a, b) = (left.bounding_box(), right.bounding_box());
+        let bbox = match operation {
+            CsgOperation::Union => AABB::box_new(&a, &b),
+            CsgOperation::Intersection => AABB::new(
+                Interval::new(a.x.min.max(b.x.min), a.x.max.min(b.x.max)),
+                Interval::new(a.y.min.max(b.y.min), a.y.max.min(b.y.max)),
+                Interval::new(a.z.min.max(b.z.min), a.z.max.min(b.z.max)),
+            ),
+            CsgOperation::Difference => a,
+        };
+        Self {
+            left,
+            right,
+            operation,
+            bbox,
+        }
+    }
+    pub fn union(left: Arc<dyn Hittable>, right: Arc<dyn Hittable>) -> Self {
+        Self::new(CsgOperation::Union, left, right)
+    }
+    pub fn intersection(left: Arc<dyn Hittable>, right: Arc<dyn Hittable>) -> Self {
+        Self::new(CsgOperation::Intersection, left, right)
+    }
+    pub fn difference(left: Arc<dyn Hittable>, right: Arc<dyn Hittable>) -> Self {
+        Self::new(CsgOperation::Difference, left, right)
+    }
+}
+
+// Whether the ray starts inside `object`, told by its first crossing past
+// `ray_t.min` however far away, and its crossings within `ray_t`.
+fn child_crossings(object: &dyn Hittable, r: &Ray, ray_t: &Interval) -> (bool, Vec<HitRecord>) {
+    match object.hit(r, &Interval::new(ray_t.min, INFINITY)) {
+        None => (false, Vec::new()),
+        Some(first) if first.t >= ray_t.max => (!first.front_face, Vec::new()),
+        Some(first) => (!first.front_face, object.hit_all(r, ray_t)),
+    }
+}
+
+impl Csg {
+    // Walks the crossings of both children in order, tracking whether the ray is
+    // inside each, and keeps those where the combined solid is entered or left,
+    // stopping after the first when `first_only` is set.
+    fn crossings(&self, r: &Ray, ray_t: &Interval, first_only: bool) -> Vec<HitRecord> {
+        let (mut in_left, left) = child_crossings(self.left.as_ref(), r, ray_t);
+        let (mut in_right, right) = child_crossings(self.right.as_ref(), r, ray_t);
+        let mut inside = self.operation.contains(in_left, in_right);
+
+        let mut crossings = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < left.len() || j < right.len() {
+            let rec = if j == right.len() || (i < left.len() && left[i].t <= right[j].t) {
+                i += 1;
+                in_left = left[i - 1].front_face;
+                &left[i - 1]
+            } else {
+                j += 1;
+                in_right = right[j - 1].front_face;
+                &right[j - 1]
+            };
+            let now_inside = self.operation.contains(in_left, in_right);
+            if now_inside != inside {
+                // The normal already faces the ray, only the side it is on changes.
+                let mut rec = rec.clone();
+                rec.front_face = now_inside;
+                crossings.push(rec);
+                inside = now_inside;
+                if first_only {
+                    break;
+                }
+            }
+        }
+        crossings
+    }
+}
+
+impl Hittable for Csg {
+    fn hit(&self, r: &Ray, ray_t: &Interval) -> Option<HitRecord> {
+        self.crossings(r, ray_t, true).into_iter().next()
+    }
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+    fn hit_all(&self, r: &Ray, ray_t: &Interval) -> Vec<HitRecord> {
+        self.crossings(r, ray_t, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::vec3::Vector;
+
+    // Unit spheres around the origin and (1, 0, 0), crossed along +x by a ray
+    // from x = -5: the first spans t in [4, 6], the second [5, 7].
+    fn spheres(operation: CsgOperation) -> Csg {
+        let mat = Arc::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5)));
+        Csg::new(
+            operation,
+            Arc::new(Sphere::new(Vector::new(0.0, 0.0, 0.0), 1.0, mat.clone())),
+            Arc::new(Sphere::new(Vector::new(1.0, 0.0, 0.0), 1.0, mat)),
+        )
+    }
+
+    fn ray_from(x: f64) -> Ray {
+        Ray::new(Vector::new(x, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0), 0.0)
+    }
+
+    // The t and front_face of every crossing, checking that normals face the ray.
+    fn crossings(csg: &Csg, r: &Ray, ray_t: &Interval) -> Vec<(f64, bool)> {
+        csg.hit_all(r, ray_t)
+            .iter()
+            .map(|rec| {
+                assert!((rec.normal - Vector::new(-1.0, 0.0, 0.0)).near_zero());
+                ((rec.t * 1e9).round() / 1e9, rec.front_face)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn operations_enter_and_leave_at_the_right_surfaces() {
+        let ray_t = Interval::new(0.001, INFINITY);
+        let expected = [
+            (CsgOperation::Union, [(4.0, true), (7.0, false)]),
+            (CsgOperation::Intersection, [(5.0, true), (6.0, false)]),
+            (CsgOperation::Difference, [(4.0, true), (5.0, false)]),
+        ];
+        for (operation, hits) in expected {
+            let csg = spheres(operation);
+            assert_eq!(crossings(&csg, &ray_from(-5.0), &ray_t), hits.to_vec());
+            let first = csg.hit(&ray_from(-5.0), &ray_t).unwrap();
+            assert!((first.t - hits[0].0).abs() < 1e-9 && first.front_face);
+        }
+    }
+
+    #[test]
+    fn rays_starting_inside_only_leave() {
+        let ray_t = Interval::new(0.001, INFINITY);
+        let r = ray_from(0.5);
+        let union = spheres(CsgOperation::Union);
+        assert_eq!(crossings(&union, &r, &ray_t), vec![(1.5, false)]);
+        let intersection = spheres(CsgOperation::Intersection);
+        assert_eq!(crossings(&intersection, &r, &ray_t), vec![(0.5, false)]);
+        let difference = spheres(CsgOperation::Difference);
+        assert!(crossings(&difference, &r, &ray_t).is_empty());
+    }
+
+    #[test]
+    fn crossings_past_the_interval_are_left_out() {
+        let union = spheres(CsgOperation::Union);
+        let short = Interval::new(0.001, 6.5);
+        assert_eq!(
+            crossings(&union, &ray_from(-5.0), &short),
+            vec![(4.0, true)]
+        );
+        // Inside both spheres with every crossing beyond reach.
+        assert!(union
+            .hit(&ray_from(0.5), &Interval::new(0.001, 0.25))
+            .is_none());
+        let difference = spheres(CsgOperation::Difference);
+        assert!(difference
+            .hit(&ray_from(-5.0), &Interval::new(0.001, 3.0))
+            .is_none());
+    }
+}
@@ -0,0 +1,451 @@
+use crate::aabb::AABB;
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::vec3::Vector;
+
+use std::f64::consts::SQRT_2;
+use std::sync::Arc;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum CurveType {
+    // A flat strip that always faces the ray, cheap for grass and distant hair.
+    Ribbon,
+    // A round tube whose width is the diameter, for cables and close-up hair.
+    Tube,
+}
+
+// A cubic Bézier curve through `points` whose width varies linearly from
+// `widths.0` to `widths.1`. A curve may be one piece [u_min, u_max] of a longer
+// one, see `Curve::segments`; u runs along the whole curve and v across it.
+pub struct Curve {
+    pub points: [Vector; 4],
+    pub widths: (f64, f64),
+    pub u_min: f64,
+    pub u_max: f64,
+    pub kind: CurveType,
+    pub mat: Arc<dyn Material>,
+    max_depth: u32,
+    bbox: AABB,
+}
+
+impl Curve {
+    pub fn new(
+        points: [Vector; 4],
+        widths: (f64, f64),
+        kind: CurveType,
+        mat: Arc<dyn Material>,
+    ) -> Self {
+        Self::segment_new(points, widths, 0.0, 1.0, kind, mat)
+    }
+    pub fn segment_new(
+        points: [Vector; 4],
+        widths: (f64, f64),
+        u_min: f64,
+        u_max: f64,
+        kind: CurveType,
+        mat: Arc<dyn Material>,
+    ) -> Self {
+        let points = [
+            blossom(&points, u_min, u_min, u_min),
+            blossom(&points, u_min, u_min, u_max),
+            blossom(&points, u_min, u_max, u_max),
+            blossom(&points, u_max, u_max, u_max),
+        ];
+        // Subdivide until the pieces deviate from straight segments by less than
+        // a twentieth of the width (Pharr et al., Physically Based Rendering).
+        let mut bend: f64 = 0.0;
+        for i in 0..2 {
+            let d = points[i] - points[i + 1] * 2.0 + points[i + 2];
+            bend = bend.max(d.x.abs()).max(d.y.abs()).max(d.z.abs());
+        }
+        let epsilon = 0.05 * widths.0.max(widths.1);
+        let levels = (SQRT_2 * 6.0 * bend / (8.0 * epsilon)).log2() / 2.0;
+        let max_depth = if levels.is_finite() {
+            levels.clamp(0.0, 10.0) as u32
+        } else {
+            0
+        };
+        let half = 0.5 * widths.0.max(widths.1);
+        let pad = Vector::new(half, half, half);
+        let mut bbox = AABB::default();
+        for p in points.iter() {
+            bbox = AABB::box_new(&bbox, &AABB::point_new(&(*p - pad), &(*p + pad)));
+        }
+        Self {
+            points,
+            widths,
+            u_min,
+            u_max,
+            kind,
+            mat,
+            max_depth,
+            bbox,
+        }
+    }
+    // Splits a long curve into `count` pieces so that a BVH can bound it tightly.
+    pub fn segments(
+        points: [Vector; 4],
+        widths: (f64, f64),
+        count: usize,
+        kind: CurveType,
+        mat: Arc<dyn Material>,
+    ) -> HittableList {
+        let mut list = HittableList::new();
+        for k in 0..count {
+            list.add(Arc::new(Self::segment_new(
+                points,
+                widths,
+                k as f64 / count as f64,
+                (k + 1) as f64 / count as f64,
+                kind,
+                mat.clone(),
+            )));
+        }
+        list
+    }
+    // Gauss-Newton steps from `u` towards the parameter of the curve point
+    // nearest to `p`.
+    fn closest_parameter(&self, p: Vector, mut u: f64) -> f64 {
+        for _ in 0..4 {
+            let (center, tangent) = evaluate(&self.points, u);
+            let speed = tangent.length_square();
+            if speed == 0.0 {
+                break;
+            }
+            u = (u + tangent.dot(&(p - center)) / speed).clamp(0.0, 1.0);
+        }
+        u
+    }
+    fn width(&self, u: f64) -> f64 {
+        self.widths.0 + (self.widths.1 - self.widths.0) * u
+    }
+    // `cp` are control points in a frame where the ray starts at the origin and
+    // runs along +z at unit speed, so z is the distance along it. Returns the
+    // distance and the local parameter and signed offset of the closest hit.
+    fn recursive_hit(
+        &self,
+        cp: &[Vector; 4],
+        z_range: &Interval,
+        u0: f64,
+        u1: f64,
+        depth: u32,
+    ) -> Option<(f64, f64, f64)> {
+        let half = 0.5
+            * self
+                .width(self.u_min + (self.u_max - self.u_min) * u0)
+                .max(self.width(self.u_min + (self.u_max - self.u_min) * u1));
+        let (mut low, mut high) = (cp[0], cp[0]);
+        for p in cp.iter() {
+            low = Vector::new(low.x.min(p.x), low.y.min(p.y), low.z.min(p.z));
+            high = Vector::new(high.x.max(p.x), high.y.max(p.y), high.z.max(p.z));
+        }
+        if low.x - half > 0.0
+            || high.x + half < 0.0
+            || low.y - half > 0.0
+            || high.y + half < 0.0
+            || high.z + half < z_range.min
+            || low.z - half > z_range.max
+        {
+            return None;
+        }
+
+        if depth > 0 {
+            let split = subdivide(cp);
+            let middle = 0.5 * (u0 + u1);
+            let first = [split[0], split[1], split[2], split[3]];
+            let second = [split[3], split[4], split[5], split[6]];
+            let near = self.recursive_hit(&first, z_range, u0, middle, depth - 1);
+            let z_max = near.map_or(z_range.max, |(z, _, _)| z);
+            let far = self.recursive_hit(
+                &second,
+                &Interval::new(z_range.min, z_max),
+                middle,
+                u1,
+                depth - 1,
+            );
+            return far.or(near);
+        }
+
+        // The ray must pass between the planes perpendicular to the curve at
+        // both ends of this nearly straight piece.
+        let start_edge = (cp[1].y - cp[0].y) * -cp[0].y + cp[0].x * (cp[0].x - cp[1].x);
+        let end_edge = (cp[2].y - cp[3].y) * -cp[3].y + cp[3].x * (cp[3].x - cp[2].x);
+        if start_edge < 0.0 || end_edge < 0.0 {
+            return None;
+        }
+        let segment = Vector::new(cp[3].x - cp[0].x, cp[3].y - cp[0].y, 0.0);
+        let length_square = segment.length_square();
+        if length_square == 0.0 {
+            return None;
+        }
+        let w = ((-cp[0].x * segment.x - cp[0].y * segment.y) / length_square).clamp(0.0, 1.0);
+        let local_u = u0 + (u1 - u0) * w;
+        let half = 0.5 * self.width(self.u_min + (self.u_max - self.u_min) * local_u);
+        let (pc, tangent) = evaluate(cp, w);
+        let distance_square = pc.x * pc.x + pc.y * pc.y;
+        if distance_square > half * half {
+            return None;
+        }
+        let z = match self.kind {
+            CurveType::Ribbon => pc.z,
+            CurveType::Tube => pc.z - (half * half - distance_square).sqrt(),
+        };
+        if !z_range.surrounds(z) {
+            return None;
+        }
+        // Which side of the centerline, as seen along the ray, the ray passes.
+        let side = if tangent.x * pc.y - tangent.y * pc.x > 0.0 {
+            1.0
+        } else {
+            -1.0
+        };
+        Some((z, local_u, side * distance_square.sqrt() / half))
+    }
+}
+
+fn lerp(a: Vector, b: Vector, t: f64) -> Vector {
+    a * (1.0 - t) + b * t
+}
+
+// The de Casteljau construction with a different parameter at every level.
+fn blossom(p: &[Vector; 4], u0: f64, u1: f64, u2: f64) -> Vector {
+    let a = [
+        lerp(p[0], p[1], u0),
+        lerp(p[1], p[2], u0),
+        lerp(p[2], p[3], u0),
+    ];
+    let b = [lerp(a[0], a[1], u1), lerp(a[1], a[2], u1)];
+    lerp(b[0], b[1], u2)
+}
+
+// Control points of the two halves, sharing the middle one.
+fn subdivide(p: &[Vector; 4]) -> [Vector; 7] {
+    [
+        p[0],
+        (p[0] + p[1]) / 2.0,
+        (p[0] + p[1] * 2.0 + p[2]) / 4.0,
+        (p[0] + p[1] * 3.0 + p[2] * 3.0 + p[3]) / 8.0,
+        (p[1] + p[2] * 2.0 + p[3]) / 4.0,
+        (p[2] + p[3]) / 2.0,
+        p[3],
+    ]
+}
+
+// The point and tangent at u.
+fn evaluate(p: &[Vector; 4], u: f64) -> (Vector, Vector) {
+    let a = [
+        lerp(p[0], p[1], u),
+        lerp(p[1], p[2], u),
+        lerp(p[2], p[3], u),
+    ];
+    let b = [lerp(a[0], a[1], u), lerp(a[1], a[2], u)];
+    (lerp(b[0], b[1], u), (b[1] - b[0]) * 3.0)
+}
+
+impl Hittable for Curve {
+    fn hit(&self, r: &Ray, ray_t: &Interval) -> Option<HitRecord> {
+        let length = r.direction.length();
+        let mut frame: Onb = Default::default();
+        frame.build_from_w(r.direction);
+        let cp = self.points.map(|p| frame.coords(p - r.origin));
+        let z_range = Interval::new(ray_t.min * length, ray_t.max * length);
+        let (z, mut local_u, offset) =
+            self.recursive_hit(&cp, &z_range, 0.0, 1.0, self.max_depth)?;
+
+        let mut t = z / length;
+        if self.kind == CurveType::Tube {
+            // The hit is placed as if the ray crossed the tube square on and the
+            // parameter is where it passes closest to the curve. A few Newton steps
+            // on the distance to the centerline correct both for oblique rays.
+            for _ in 0..4 {
+                local_u = self.closest_parameter(r.at(t), local_u);
+                let (center, _) = evaluate(&self.points, local_u);
+                let radial = r.at(t) - center;
+                let slope = radial.unit().dot(&r.direction);
+                if radial.near_zero() || slope.abs() < 1e-9 {
+                    break;
+                }
+                let half = 0.5 * self.width(self.u_min + (self.u_max - self.u_min) * local_u);
+                t -= (radial.length() - half) / slope;
+            }
+            if !ray_t.surrounds(t) {
+                return None;
+            }
+        }
+        let mut rec = HitRecord::new(r.at(t), Vector::default(), t, false);
+        let (center, tangent) = evaluate(&self.points, local_u);
+        let outward_normal = match self.kind {
+            CurveType::Ribbon => {
+                let toward = r.direction.unit() * -1.0;
+                let tangent = tangent.unit();
+                let facing = toward - tangent * toward.dot(&tangent);
+                if facing.near_zero() {
+                    toward
+                } else {
+                    facing.unit()
+                }
+            }
+            CurveType::Tube => {
+                let radial = rec.p - center;
+                if radial.near_zero() {
+                    r.direction.unit() * -1.0
+                } else {
+                    radial.unit()
+                }
+            }
+        };
+        rec.set_face_normal(r, &outward_normal);
+        rec.u = self.u_min + (self.u_max - self.u_min) * local_u;
+        rec.v = 0.5 + 0.5 * offset;
+        // v runs across the width as seen along the ray. As in pbrt, its
+        // derivative is kept in the tangent plane, so on a tube it turns towards
+        // or away from the ray rather than growing without bound at the sides.
+        rec.dpdu = tangent / (self.u_max - self.u_min);
+        let across = outward_normal.cross(&tangent);
+        if !across.near_zero() {
+            let across = across.unit() * self.width(rec.u);
+            rec.dpdv = if across.dot(&r.direction.cross(&tangent)) < 0.0 {
+                across * -1.0
+            } else {
+                across
+            };
+        }
+        rec.mat = Some(self.mat.clone());
+        Some(rec)
+    }
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::rtweekend::{solve_quadratic, INFINITY};
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5)))
+    }
+
+    // From (0, 0, 0) to (3, 0, 0) with evenly spaced control points, so that u
+    // is x / 3.
+    fn straight(widths: (f64, f64), kind: CurveType) -> Curve {
+        let points = [0.0, 1.0, 2.0, 3.0].map(|x| Vector::new(x, 0.0, 0.0));
+        Curve::new(points, widths, kind, material())
+    }
+
+    #[test]
+    fn straight_ribbon_is_as_wide_as_its_widths() {
+        let ribbon = straight((0.4, 0.2), CurveType::Ribbon);
+        let ray_t = Interval::new(0.001, INFINITY);
+        for x in [0.3, 1.2, 2.7] {
+            let half = 0.5 * (0.4 - 0.2 * x / 3.0);
+            for y in [
+                -1.2 * half,
+                -0.9 * half,
+                0.0,
+                0.5 * half,
+                0.9 * half,
+                1.2 * half,
+            ] {
+                let r = Ray::new(Vector::new(x, y, 5.0), Vector::new(0.0, 0.0, -2.0), 0.0);
+                match ribbon.hit(&r, &ray_t) {
+                    Some(rec) => {
+                        assert!(y.abs() < half, "x = {}, y = {}", x, y);
+                        assert!((rec.t - 2.5).abs() < 1e-9);
+                        assert!((rec.u - x / 3.0).abs() < 1e-9);
+                        assert!((rec.normal - Vector::new(0.0, 0.0, 1.0)).near_zero());
+                        assert!(((2.0 * rec.v - 1.0).abs() - y.abs() / half).abs() < 1e-9);
+                    }
+                    None => assert!(y.abs() > half, "x = {}, y = {}", x, y),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn straight_tube_is_hit_like_a_cylinder() {
+        let tube = straight((0.4, 0.4), CurveType::Tube);
+        let ray_t = Interval::new(0.001, INFINITY);
+        for (origin, direction) in [
+            (Vector::new(1.5, 0.1, 5.0), Vector::new(0.0, 0.0, -1.0)),
+            (Vector::new(0.8, 1.0, 5.0), Vector::new(0.3, -0.2, -1.0)),
+            (Vector::new(2.0, -0.15, -3.0), Vector::new(-0.1, 0.0, 1.0)),
+        ] {
+            let r = Ray::new(origin, direction, 0.0);
+            let rec = tube.hit(&r, &ray_t).unwrap();
+            // The cylinder of radius 0.2 around the x axis.
+            let a = direction.y * direction.y + direction.z * direction.z;
+            let b = 2.0 * (origin.y * direction.y + origin.z * direction.z);
+            let c = origin.y * origin.y + origin.z * origin.z - 0.04;
+            let (t, _) = solve_quadratic(a, b, c).unwrap();
+            assert!((rec.t - t).abs() < 1e-6, "{} != {}", rec.t, t);
+            let p = r.at(t);
+            let normal = Vector::new(0.0, p.y, p.z) / 0.2;
+            assert!((rec.normal - normal).length() < 1e-6);
+            assert!(rec.front_face);
+        }
+        let r = Ray::new(
+            Vector::new(1.5, 0.25, 5.0),
+            Vector::new(0.0, 0.0, -1.0),
+            0.0,
+        );
+        assert!(tube.hit(&r, &ray_t).is_none());
+    }
+
+    #[test]
+    fn bounding_box_covers_the_hull_and_the_width() {
+        let points = [
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(1.0, 2.0, -1.0),
+            Vector::new(2.0, -1.5, 0.5),
+            Vector::new(3.0, 0.5, 1.0),
+        ];
+        for (u_min, u_max) in [(0.0, 1.0), (0.25, 0.5)] {
+            let curve = Curve::segment_new(
+                points,
+                (0.1, 0.3),
+                u_min,
+                u_max,
+                CurveType::Tube,
+                material(),
+            );
+            let bbox = curve.bounding_box();
+            let inside = |p: Vector| {
+                let slack = 1e-12;
+                bbox.x.min - slack <= p.x
+                    && p.x <= bbox.x.max + slack
+                    && bbox.y.min - slack <= p.y
+                    && p.y <= bbox.y.max + slack
+                    && bbox.z.min - slack <= p.z
+                    && p.z <= bbox.z.max + slack
+            };
+            let half = 0.5 * 0.3;
+            for p in curve.points.iter() {
+                for offset in [-half, half] {
+                    assert!(inside(*p + Vector::new(offset, offset, offset)));
+                }
+            }
+            for k in 0..=50 {
+                let u = u_min + (u_max - u_min) * k as f64 / 50.0;
+                let (center, _) = evaluate(&points, u);
+                let half = 0.5 * curve.width(u);
+                for offset in [
+                    Vector::new(half, 0.0, 0.0),
+                    Vector::new(-half, 0.0, 0.0),
+                    Vector::new(0.0, half, 0.0),
+                    Vector::new(0.0, -half, 0.0),
+                    Vector::new(0.0, 0.0, half),
+                    Vector::new(0.0, 0.0, -half),
+                ] {
+                    assert!(inside(center + offset), "u = {}", u);
+                }
+            }
+        }
+    }
+}
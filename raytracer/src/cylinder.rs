@@ -0,0 +1,198 @@
+use crate::aabb::AABB;
+use crate::disk::Disk;
+use crate::hittable::{area_pdf_value, HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::rtweekend::{random_double, solve_quadratic, PI};
+use crate::vec3::Vector;
+
+use std::sync::Arc;
+
+// A cylinder of `radius` from `base` to `base + axis`, optionally closed by two
+// disks. The side maps u around the axis and v from base to top.
+pub struct Cylinder {
+    pub base: Vector,
+    pub frame: Onb,
+    pub height: f64,
+    pub radius: f64,
+    pub caps: Vec<Disk>,
+    pub mat: Arc<dyn Material>,
+    pub bbox: AABB,
+    pub side_area: f64,
+    pub area: f64,
+}
+
+impl Cylinder {
+    pub fn new(
+        base: Vector,
+        axis: Vector,
+        radius: f64,
+        capped: bool,
+        mat: Arc<dyn Material>,
+    ) -> Self {
+        let mut frame: Onb = Default::default();
+        frame.build_from_w(axis);
+        let top = base + axis;
+        let caps = if capped {
+            vec![
+                Disk::new(base, axis * -1.0, radius, mat.clone()),
+                Disk::new(top, axis, radius, mat.clone()),
+            ]
+        } else {
+            Vec::new()
+        };
+        let side_area = 2.0 * PI * radius * axis.length();
+        Self {
+            base,
+            frame,
+            height: axis.length(),
+            radius,
+            area: side_area + caps.iter().map(|cap| cap.area).sum::<f64>(),
+            caps,
+            mat,
+            bbox: AABB::box_new(
+                &Disk::circle_bounding_box(base, frame.axis[2], radius),
+                &Disk::circle_bounding_box(top, frame.axis[2], radius),
+            ),
+            side_area,
+        }
+    }
+    fn hit_side(&self, r: &Ray, ray_t: &Interval) -> Option<HitRecord> {
+        let o = self.frame.coords(r.origin - self.base);
+        let d = self.frame.coords(r.direction);
+        let a = d.x * d.x + d.y * d.y;
+        let b = 2.0 * (o.x * d.x + o.y * d.y);
+        let c = o.x * o.x + o.y * o.y - self.radius * self.radius;
+        let (t0, t1) = solve_quadratic(a, b, c)?;
+        for t in [t0, t1] {
+            let z = o.z + t * d.z;
+            if !ray_t.surrounds(t) || z < 0.0 || z > self.height {
+                continue;
+            }
+            let x = o.x + t * d.x;
+            let y = o.y + t * d.y;
+            let mut rec = HitRecord::new(r.at(t), Vector::default(), t, false);
+            let outward_normal = self.frame.local(x, y, 0.0) / self.radius;
+            rec.set_face_normal(r, &outward_normal);
+            rec.u = (y.atan2(x) + PI) / (2.0 * PI);
+            rec.v = z / self.height;
+            rec.dpdu = self.frame.local(-y, x, 0.0) * (2.0 * PI);
+            rec.dpdv = self.frame.axis[2] * self.height;
+            rec.mat = Some(self.mat.clone());
+            return Some(rec);
+        }
+        None
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, r: &Ray, ray_t: &Interval) -> Option<HitRecord> {
+        let mut closest = self.hit_side(r, ray_t);
+        for cap in self.caps.iter() {
+            let t_max = closest.as_ref().map_or(ray_t.max, |rec| rec.t);
+            if let Some(rec) = cap.hit(r, &Interval::new(ray_t.min, t_max)) {
+                closest = Some(rec);
+            }
+        }
+        closest
+    }
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+    fn pdf_value(&self, origin: Vector, direction: Vector) -> f64 {
+        area_pdf_value(self, self.area, origin, direction)
+    }
+    fn random(&self, origin: Vector) -> Vector {
+        let mut pick = random_double() * self.area - self.side_area;
+        if pick >= 0.0 {
+            for cap in self.caps.iter() {
+                if pick < cap.area {
+                    return cap.random(origin);
+                }
+                pick -= cap.area;
+            }
+        }
+        let phi = 2.0 * PI * random_double();
+        let p = self.frame.local(
+            self.radius * phi.cos(),
+            self.radius * phi.sin(),
+            self.height * random_double(),
+        );
+        self.base + p - origin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::assert_pdf_matches_random;
+    use crate::material::Lambertian;
+    use crate::rtweekend::INFINITY;
+
+    // Radius 1 from y = 0 to y = 2, around the y axis.
+    fn cylinder(capped: bool) -> Cylinder {
+        let mat: Arc<dyn Material> = Arc::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5)));
+        Cylinder::new(
+            Vector::default(),
+            Vector::new(0.0, 2.0, 0.0),
+            1.0,
+            capped,
+            mat,
+        )
+    }
+
+    #[test]
+    fn side_hits_face_away_from_the_axis() {
+        let r = Ray::new(Vector::new(5.0, 1.5, 0.0), Vector::new(-2.0, 0.0, 0.0), 0.0);
+        let rec = cylinder(true)
+            .hit(&r, &Interval::new(0.001, INFINITY))
+            .unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-12);
+        assert!(rec.front_face);
+        assert!((rec.normal - Vector::new(1.0, 0.0, 0.0)).near_zero());
+        assert!((rec.v - 0.75).abs() < 1e-12);
+        // From the axis the wall is left through its back face.
+        let r = Ray::new(Vector::new(0.0, 1.0, 0.0), Vector::new(0.0, 0.0, 1.0), 0.0);
+        let rec = cylinder(true)
+            .hit(&r, &Interval::new(0.001, INFINITY))
+            .unwrap();
+        assert!((rec.t - 1.0).abs() < 1e-12);
+        assert!(!rec.front_face);
+        assert!((rec.normal - Vector::new(0.0, 0.0, -1.0)).near_zero());
+    }
+
+    #[test]
+    fn caps_close_only_capped_cylinders() {
+        let r = Ray::new(
+            Vector::new(0.3, 5.0, -0.2),
+            Vector::new(0.0, -1.0, 0.0),
+            0.0,
+        );
+        let rec = cylinder(true)
+            .hit(&r, &Interval::new(0.001, INFINITY))
+            .unwrap();
+        assert!((rec.t - 3.0).abs() < 1e-12);
+        assert!((rec.normal - Vector::new(0.0, 1.0, 0.0)).near_zero());
+        let r = Ray::new(
+            Vector::new(0.3, -4.0, -0.2),
+            Vector::new(0.0, 1.0, 0.0),
+            0.0,
+        );
+        let rec = cylinder(true)
+            .hit(&r, &Interval::new(0.001, INFINITY))
+            .unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-12);
+        assert!((rec.normal - Vector::new(0.0, -1.0, 0.0)).near_zero());
+        assert!(cylinder(false)
+            .hit(&r, &Interval::new(0.001, INFINITY))
+            .is_none());
+    }
+
+    #[test]
+    fn pdf_matches_random() {
+        assert_pdf_matches_random(&cylinder(true), Vector::new(2.0, 3.0, 1.5));
+        assert_pdf_matches_random(&cylinder(false), Vector::new(2.0, 3.0, 1.5));
+    }
+}
@@ -0,0 +1,165 @@
+use crate::aabb::AABB;
+use crate::hittable::{area_pdf_value, HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::rtweekend::{random_double, PI};
+use crate::vec3::Vector;
+
+use std::sync::Arc;
+
+// A flat disk around `center` facing `normal`; a nonzero `inner_radius` makes it
+// an annulus. u runs around the center and v outwards from the inner edge.
+pub struct Disk {
+    pub center: Vector,
+    pub frame: Onb,
+    pub inner_radius: f64,
+    pub radius: f64,
+    pub mat: Arc<dyn Material>,
+    pub bbox: AABB,
+    pub area: f64,
+}
+
+impl Disk {
+    pub fn new(center: Vector, normal: Vector, radius: f64, mat: Arc<dyn Material>) -> Self {
+        Self::annulus_new(center, normal, 0.0, radius, mat)
+    }
+    pub fn annulus_new(
+        center: Vector,
+        normal: Vector,
+        inner_radius: f64,
+        radius: f64,
+        mat: Arc<dyn Material>,
+    ) -> Self {
+        let mut frame: Onb = Default::default();
+        frame.build_from_w(normal);
+        Self {
+            center,
+            frame,
+            inner_radius,
+            radius,
+            mat,
+            bbox: Self::circle_bounding_box(center, frame.axis[2], radius),
+            area: PI * (radius * radius - inner_radius * inner_radius),
+        }
+    }
+    // A circle around a unit `axis` reaches radius * sin(angle to the axis) along
+    // each coordinate direction.
+    pub fn circle_bounding_box(center: Vector, axis: Vector, radius: f64) -> AABB {
+        let extent = Vector::new(
+            radius * (1.0 - axis.x * axis.x).max(0.0).sqrt(),
+            radius * (1.0 - axis.y * axis.y).max(0.0).sqrt(),
+            radius * (1.0 - axis.z * axis.z).max(0.0).sqrt(),
+        );
+        AABB::point_new(&(center - extent), &(center + extent))
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, r: &Ray, ray_t: &Interval) -> Option<HitRecord> {
+        let origin = self.frame.coords(r.origin - self.center);
+        let direction = self.frame.coords(r.direction);
+        if direction.z.abs() < 1e-12 {
+            return None;
+        }
+        let t = -origin.z / direction.z;
+        if !ray_t.surrounds(t) {
+            return None;
+        }
+        let x = origin.x + t * direction.x;
+        let y = origin.y + t * direction.y;
+        let distance = (x * x + y * y).sqrt();
+        if distance > self.radius || distance < self.inner_radius {
+            return None;
+        }
+        let mut rec = HitRecord::new(r.at(t), Vector::default(), t, false);
+        rec.set_face_normal(r, &self.frame.axis[2]);
+        rec.u = (y.atan2(x) + PI) / (2.0 * PI);
+        rec.v = (distance - self.inner_radius) / (self.radius - self.inner_radius);
+        let phi = y.atan2(x);
+        rec.dpdu = self.frame.local(-y, x, 0.0) * (2.0 * PI);
+        rec.dpdv = self.frame.local(phi.cos(), phi.sin(), 0.0) * (self.radius - self.inner_radius);
+        rec.mat = Some(self.mat.clone());
+        Some(rec)
+    }
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+    fn pdf_value(&self, origin: Vector, direction: Vector) -> f64 {
+        area_pdf_value(self, self.area, origin, direction)
+    }
+    fn random(&self, origin: Vector) -> Vector {
+        let inner = self.inner_radius * self.inner_radius;
+        let distance = (inner + random_double() * (self.radius * self.radius - inner)).sqrt();
+        let phi = 2.0 * PI * random_double();
+        self.center
+            + self
+                .frame
+                .local(distance * phi.cos(), distance * phi.sin(), 0.0)
+            - origin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::assert_pdf_matches_random;
+    use crate::material::Lambertian;
+    use crate::rtweekend::INFINITY;
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn annulus_is_hit_between_its_radii() {
+        let center = Vector::new(1.0, 2.0, 3.0);
+        let ring = Disk::annulus_new(center, Vector::new(0.0, 0.0, 1.0), 0.5, 2.0, material());
+        let ray_t = Interval::new(0.001, INFINITY);
+        let down = Vector::new(0.0, 0.0, -1.0);
+        let rec = ring
+            .hit(&Ray::new(Vector::new(2.0, 2.0, 10.0), down, 0.0), &ray_t)
+            .unwrap();
+        assert!((rec.t - 7.0).abs() < 1e-12);
+        assert!(rec.front_face);
+        assert!((rec.normal - Vector::new(0.0, 0.0, 1.0)).near_zero());
+        assert!((rec.v - 1.0 / 3.0).abs() < 1e-12);
+        for x in [1.0, 1.4, 3.1] {
+            let r = Ray::new(Vector::new(x, 2.0, 10.0), down, 0.0);
+            assert!(ring.hit(&r, &ray_t).is_none(), "x = {}", x);
+        }
+        let r = Ray::new(Vector::new(2.0, 2.0, 0.0), Vector::new(0.0, 0.0, 1.0), 0.0);
+        let rec = ring.hit(&r, &ray_t).unwrap();
+        assert!(!rec.front_face);
+        assert!((rec.normal - Vector::new(0.0, 0.0, -1.0)).near_zero());
+    }
+
+    #[test]
+    fn bounding_box_of_a_tilted_circle_is_tight() {
+        let axis = Vector::new(1.0, 1.0, 0.0).unit();
+        let bbox = Disk::circle_bounding_box(Vector::default(), axis, 2.0);
+        let s = 2.0f64.sqrt();
+        assert!((bbox.x.max - s).abs() < 1e-12 && (bbox.y.max - s).abs() < 1e-12);
+        assert!((bbox.z.max - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn pdf_matches_random() {
+        let disk = Disk::new(
+            Vector::default(),
+            Vector::new(0.0, 1.0, 1.0),
+            1.0,
+            material(),
+        );
+        assert_pdf_matches_random(&disk, Vector::new(0.5, 2.0, 0.0));
+        let ring = Disk::annulus_new(
+            Vector::default(),
+            Vector::new(0.0, 1.0, 0.0),
+            0.5,
+            1.0,
+            material(),
+        );
+        assert_pdf_matches_random(&ring, Vector::new(0.5, 1.0, 0.0));
+    }
+}
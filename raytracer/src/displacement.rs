@@ -0,0 +1,349 @@
+use crate::mesh::{smooth_normals, TriangleMesh};
+use crate::texture::Texture;
+use crate::vec3::Vector;
+
+use std::collections::HashMap;
+
+// Where a tessellated vertex came from. Vertices on the corners and edges of
+// the original triangles are named after those alone, so the faces on either
+// side of an edge share them and the surface cannot crack open.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+enum Sample {
+    Corner(u32),
+    // The k-th of the points between two vertices, counted from the first,
+    // which always has the lower index.
+    Edge(u32, u32, u32),
+    Inside(usize, u32, u32),
+}
+
+// Gray level of a texture, weighted like `rgb_to_gray`.
+pub fn texture_height(texture: &dyn Texture, u: f64, v: f64, p: Vector) -> f64 {
+    let c = texture.value(u, v, p);
+    c.x * 0.299 + c.y * 0.587 + c.z * 0.114
+}
+
+fn position_key(p: &Vector) -> (u64, u64, u64) {
+    (p.x.to_bits(), p.y.to_bits(), p.z.to_bits())
+}
+
+// The directions of the vertices at each position, summed and normalized, so
+// that copies of a vertex split for its normals or UVs move together.
+fn shared_directions(positions: &[Vector], directions: &[Vector]) -> Vec<Vector> {
+    let key = position_key;
+    let mut sums: HashMap<(u64, u64, u64), Vector> = HashMap::new();
+    for (p, direction) in positions.iter().zip(directions.iter()) {
+        let sum = sums.entry(key(p)).or_default();
+        *sum = *sum + *direction;
+    }
+    positions
+        .iter()
+        .map(|p| {
+            let sum = sums[&key(p)];
+            if sum.near_zero() {
+                sum
+            } else {
+                sum.unit()
+            }
+        })
+        .collect()
+}
+
+// UVs from the two longest axes of the bounding box of `positions`, each running
+// from 0 to 1 across it.
+fn planar_projection(positions: &[Vector]) -> impl Fn(Vector) -> (f64, f64) {
+    let mut low = Vector::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+    let mut high = Vector::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+    for p in positions.iter() {
+        low = Vector::new(low.x.min(p.x), low.y.min(p.y), low.z.min(p.z));
+        high = Vector::new(high.x.max(p.x), high.y.max(p.y), high.z.max(p.z));
+    }
+    let extent = high - low;
+    let mut axes = [0u32, 1, 2];
+    axes.sort_by(|&a, &b| extent.at(b).total_cmp(&extent.at(a)));
+    move |p: Vector| {
+        let along = |axis: u32| {
+            if extent.at(axis) > 0.0 {
+                (p.at(axis) - low.at(axis)) / extent.at(axis)
+            } else {
+                0.0
+            }
+        };
+        (along(axes[0]), along(axes[1]))
+    }
+}
+
+// Splits every edge of `mesh` into `resolution` pieces and moves each vertex
+// along its normal by `scale` times the gray level of `texture` there, so the
+// detail shows in silhouettes and shadows. The result is an ordinary mesh whose
+// triangles and bounds are those of the displaced surface, with normals taken
+// from it. The mesh's own normals give the direction, or smoothed face normals
+// if it has none, averaged over vertices that share a position, and the height
+// is sampled once per position, at the UVs of the first copy there, so that hard
+// edges and UV seams do not crack open. A mesh without UVs is textured by
+// projecting it onto the plane of its two longest bounding box axes.
+pub fn displace(
+    mesh: &TriangleMesh,
+    texture: &dyn Texture,
+    scale: f64,
+    resolution: u32,
+) -> TriangleMesh {
+    let n = resolution.max(1);
+    let directions = shared_directions(
+        &mesh.positions,
+        &if mesh.normals.is_empty() {
+            smooth_normals(&mesh.positions, &mesh.indices)
+        } else {
+            mesh.normals.clone()
+        },
+    );
+    let has_uvs = !mesh.uvs.is_empty();
+    let projection = planar_projection(&mesh.positions);
+    let has_colors = !mesh.colors.is_empty();
+
+    let mut displaced = TriangleMesh::new(
+        Vec::new(),
+        Vec::new(),
+        Vec::new(),
+        Vec::new(),
+        mesh.mat.clone(),
+    );
+    displaced.normal_map = mesh.normal_map.clone();
+    let mut vertices: HashMap<Sample, u32> = HashMap::new();
+    let mut heights: HashMap<(u64, u64, u64), f64> = HashMap::new();
+    let mut vertex = |sample: Sample, weights: &[(u32, f64)], out: &mut TriangleMesh| -> u32 {
+        *vertices.entry(sample).or_insert_with(|| {
+            let mut p = Vector::default();
+            let mut normal = Vector::default();
+            let mut uv = (0.0, 0.0);
+            let mut color = Vector::default();
+            for &(i, w) in weights.iter() {
+                let i = i as usize;
+                p = p + mesh.positions[i] * w;
+                normal = normal + directions[i] * w;
+                if has_uvs {
+                    uv = (uv.0 + mesh.uvs[i].0 * w, uv.1 + mesh.uvs[i].1 * w);
+                }
+                if has_colors {
+                    color = color + mesh.colors[i] * w;
+                }
+            }
+            if !normal.near_zero() {
+                normal = normal.unit();
+            }
+            if !has_uvs {
+                uv = projection(p);
+            }
+            let height = *heights
+                .entry(position_key(&p))
+                .or_insert_with(|| texture_height(texture, uv.0, uv.1, p));
+            out.positions.push(p + normal * (scale * height));
+            if has_uvs {
+                out.uvs.push(uv);
+            }
+            if has_colors {
+                out.colors.push(color);
+            }
+            (out.positions.len() - 1) as u32
+        })
+    };
+
+    for (f, face) in mesh.indices.iter().enumerate() {
+        // Grid point (i, j) lies i / n of the way from the first corner to the
+        // second and j / n towards the third.
+        let mut point = |i: u32, j: u32, out: &mut TriangleMesh| -> u32 {
+            let along = |from: u32, to: u32, k: u32| {
+                if from < to {
+                    Sample::Edge(from, to, k)
+                } else {
+                    Sample::Edge(to, from, n - k)
+                }
+            };
+            let (sample, weights) = if i == 0 && j == 0 {
+                (Sample::Corner(face[0]), vec![(face[0], 1.0)])
+            } else if i == n {
+                (Sample::Corner(face[1]), vec![(face[1], 1.0)])
+            } else if j == n {
+                (Sample::Corner(face[2]), vec![(face[2], 1.0)])
+            } else if j == 0 || i == 0 || i + j == n {
+                let (from, to, k) = if j == 0 {
+                    (face[0], face[1], i)
+                } else if i == 0 {
+                    (face[0], face[2], j)
+                } else {
+                    (face[1], face[2], j)
+                };
+                let sample = along(from, to, k);
+                // Weighted in the order of the end positions rather than indices,
+                // so copies of an edge split at a seam land on the same point.
+                let order = |i: u32| (position_key(&mesh.positions[i as usize]), i);
+                let (first, second, k) = if order(from) <= order(to) {
+                    (from, to, k)
+                } else {
+                    (to, from, n - k)
+                };
+                let t = k as f64 / n as f64;
+                (sample, vec![(first, 1.0 - t), (second, t)])
+            } else {
+                let (s, t) = (i as f64 / n as f64, j as f64 / n as f64);
+                (
+                    Sample::Inside(f, i, j),
+                    vec![(face[0], 1.0 - s - t), (face[1], s), (face[2], t)],
+                )
+            };
+            vertex(sample, &weights, out)
+        };
+        for j in 0..n {
+            for i in 0..n - j {
+                let a = point(i, j, &mut displaced);
+                let b = point(i + 1, j, &mut displaced);
+                let c = point(i, j + 1, &mut displaced);
+                displaced.indices.push([a, b, c]);
+                if i + j + 1 < n {
+                    let d = point(i + 1, j + 1, &mut displaced);
+                    displaced.indices.push([b, d, c]);
+                }
+            }
+        }
+    }
+    displaced.normals = smooth_normals(&displaced.positions, &displaced.indices);
+    displaced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{Lambertian, Material};
+    use crate::quad::Quad;
+    use crate::texture::SolidColor;
+    use std::sync::Arc;
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5)))
+    }
+
+    // Gray level equal to u, like an image that brightens to the right.
+    struct Ramp;
+
+    impl Texture for Ramp {
+        fn value(&self, u: f64, _v: f64, _p: Vector) -> Vector {
+            Vector::new(u, u, u)
+        }
+    }
+
+    fn bounds(mesh: &TriangleMesh) -> (Vector, Vector) {
+        let mut low = mesh.positions[0];
+        let mut high = mesh.positions[0];
+        for p in mesh.positions.iter() {
+            low = Vector::new(low.x.min(p.x), low.y.min(p.y), low.z.min(p.z));
+            high = Vector::new(high.x.max(p.x), high.y.max(p.y), high.z.max(p.z));
+        }
+        (low, high)
+    }
+
+    #[test]
+    fn constant_height_moves_a_quad_by_scale() {
+        let quad = Quad::new(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(2.0, 0.0, 0.0),
+            Vector::new(0.0, 3.0, 0.0),
+            material(),
+        );
+        let white = SolidColor::new(Vector::new(1.0, 1.0, 1.0));
+        let displaced = displace(&quad.tessellate(4), &white, 0.25, 3);
+        let (low, high) = bounds(&displaced);
+        assert_eq!((low.x, low.y, high.x, high.y), (0.0, 0.0, 2.0, 3.0));
+        assert!((low.z - 0.25).abs() < 1e-12 && (high.z - 0.25).abs() < 1e-12);
+    }
+
+    #[test]
+    fn split_vertices_move_together() {
+        // Two faces meeting at a right angle along the edge x = 0, z = 0, each
+        // with its own copies of the edge's vertices and its own flat normal.
+        let up = Vector::new(0.0, 0.0, 1.0);
+        let back = Vector::new(-1.0, 0.0, 0.0);
+        let mesh = TriangleMesh::new(
+            vec![
+                Vector::new(0.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+                Vector::new(1.0, 0.0, 0.0),
+                Vector::new(0.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+                Vector::new(0.0, 0.0, -1.0),
+            ],
+            vec![up, up, up, back, back, back],
+            Vec::new(),
+            vec![[0, 2, 1], [3, 4, 5]],
+            material(),
+        );
+        let white = SolidColor::new(Vector::new(1.0, 1.0, 1.0));
+        let displaced = displace(&mesh, &white, 0.5, 2);
+        let shift = (up + back).unit() * 0.5;
+        for expected in [0.0, 0.5, 1.0] {
+            let target = Vector::new(0.0, expected, 0.0) + shift;
+            let copies = displaced
+                .positions
+                .iter()
+                .filter(|&&p| (p - target).near_zero())
+                .count();
+            assert_eq!(copies, 2, "edge point at y = {}", expected);
+        }
+    }
+
+    #[test]
+    fn uv_seams_do_not_crack() {
+        // A square split along its diagonal, each half with its own copies of
+        // the diagonal's ends and UVs that jump from 1 to 0 across it.
+        let corners = [
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(1.0, 1.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+        ];
+        let mesh = TriangleMesh::new(
+            vec![
+                corners[0], corners[1], corners[2], corners[2], corners[3], corners[0],
+            ],
+            vec![Vector::new(0.0, 0.0, 1.0); 6],
+            vec![
+                (0.5, 0.0),
+                (1.0, 0.5),
+                (1.0, 1.0),
+                (0.0, 1.0),
+                (0.0, 0.5),
+                (0.0, 0.0),
+            ],
+            vec![[0, 1, 2], [3, 4, 5]],
+            material(),
+        );
+        let displaced = displace(&mesh, &Ramp, 1.0, 3);
+        for k in 0..=3 {
+            let t = k as f64 / 3.0;
+            let on_seam: Vec<Vector> = displaced
+                .positions
+                .iter()
+                .filter(|p| (p.x - t).abs() < 1e-12 && (p.y - t).abs() < 1e-12)
+                .cloned()
+                .collect();
+            assert_eq!(on_seam.len(), 2, "seam point {}", k);
+            assert_eq!(on_seam[0].z, on_seam[1].z, "seam point {}", k);
+        }
+    }
+
+    #[test]
+    fn meshes_without_uvs_are_projected() {
+        let quad = Quad::new(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(0.0, 0.0, 2.0),
+            Vector::new(0.0, 1.0, 0.0),
+            material(),
+        );
+        let mut mesh = quad.tessellate(2);
+        mesh.uvs.clear();
+        let displaced = displace(&mesh, &Ramp, 1.0, 1);
+        // The longest axis is z, so heights grow from 0 at z = 0 to 1 at z = 2,
+        // along the quad's normal -x.
+        for p in displaced.positions.iter() {
+            assert!((p.x + p.z / 2.0).abs() < 1e-12);
+        }
+    }
+}
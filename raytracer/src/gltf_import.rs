@@ -0,0 +1,527 @@
+use crate::bvh::BvhNode;
+use crate::camera::Camera;
+use crate::hittable::Hittable;
+use crate::hittable_list::HittableList;
+use crate::image::RtwImage;
+use crate::material::{AlphaMask, DiffuseLight, Dissolve, Material, MetallicRoughness};
+use crate::matrix::Matrix4;
+use crate::mesh::TriangleMesh;
+use crate::normal_mapping::{NormalMap, NormalMapConvention, NormalModifier};
+use crate::texture::{ImageTexture, SolidColor, Texture};
+use crate::vec3::Vector;
+use image::{DynamicImage, RgbImage};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub enum GltfError {
+    Gltf(gltf::Error),
+    Io(PathBuf, std::io::Error),
+    InvalidData(String),
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GltfError::Gltf(e) => write!(f, "failed to parse glTF: {}", e),
+            GltfError::Io(path, e) => write!(f, "failed to read '{}': {}", path.display(), e),
+            GltfError::InvalidData(msg) => write!(f, "invalid glTF data: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for GltfError {}
+
+// `camera` is the first camera met while walking the scene, `lights` holds the
+// triangles of every emissive primitive so they can be sampled directly.
+pub struct GltfScene {
+    pub world: HittableList,
+    pub lights: HittableList,
+    pub camera: Option<Camera>,
+}
+
+struct GltfMaterial {
+    mat: Arc<dyn Material>,
+    normal_map: Option<Arc<dyn NormalModifier>>,
+    emissive: bool,
+}
+
+// A decoded image as its color and, in every channel of a second texture, its
+// alpha.
+type GltfImage = (Arc<dyn Texture>, Arc<dyn Texture>);
+
+struct Importer<'a> {
+    dir: PathBuf,
+    buffers: Vec<gltf::buffer::Data>,
+    document: &'a gltf::Document,
+    images: HashMap<usize, Option<GltfImage>>,
+    materials: HashMap<Option<usize>, Arc<GltfMaterial>>,
+    meshes: Vec<(TriangleMesh, bool)>,
+    camera: Option<Camera>,
+}
+
+// Loads `.gltf` (with external or data URI buffers) and `.glb` files. Node
+// transforms are applied to the vertices, so the meshes land in world space;
+// images that cannot be read fall back to the material factors.
+pub fn load_gltf<P: AsRef<Path>>(path: P) -> Result<GltfScene, GltfError> {
+    let path = path.as_ref();
+    let gltf = gltf::Gltf::open(path).map_err(|e| match e {
+        gltf::Error::Io(e) => GltfError::Io(path.to_path_buf(), e),
+        e => GltfError::Gltf(e),
+    })?;
+    let dir = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+    let buffers = gltf::import_buffers(&gltf.document, Some(&dir), gltf.blob.clone())
+        .map_err(GltfError::Gltf)?;
+    let mut importer = Importer {
+        dir,
+        buffers,
+        document: &gltf.document,
+        images: HashMap::new(),
+        materials: HashMap::new(),
+        meshes: Vec::new(),
+        camera: None,
+    };
+    let scene = gltf
+        .document
+        .default_scene()
+        .or_else(|| gltf.document.scenes().next());
+    if let Some(scene) = scene {
+        for node in scene.nodes() {
+            importer.visit(&node, Matrix4::identity())?;
+        }
+    }
+
+    let mut objects: Vec<Arc<dyn Hittable>> = Vec::new();
+    let mut lights = HittableList::new();
+    for (mesh, emissive) in importer.meshes {
+        let mesh = Arc::new(mesh);
+        objects.extend(TriangleMesh::triangles(&mesh));
+        if emissive {
+            for triangle in TriangleMesh::triangles(&mesh) {
+                lights.add(triangle);
+            }
+        }
+    }
+    let mut world = HittableList::new();
+    if !objects.is_empty() {
+        let len = objects.len();
+        world.add(Arc::new(BvhNode::new(&mut objects, 0, len)));
+    }
+    Ok(GltfScene {
+        world,
+        lights,
+        camera: importer.camera,
+    })
+}
+
+impl<'a> Importer<'a> {
+    fn visit(&mut self, node: &gltf::Node, parent: Matrix4) -> Result<(), GltfError> {
+        let local = node.transform().matrix();
+        let mut m = Matrix4::identity();
+        for (i, row) in m.m.iter_mut().enumerate() {
+            for (j, x) in row.iter_mut().enumerate() {
+                *x = local[j][i] as f64;
+            }
+        }
+        let world = parent * m;
+        if let Some(mesh) = node.mesh() {
+            self.add_mesh(&mesh, &world)?;
+        }
+        if let (None, Some(camera)) = (&self.camera, node.camera()) {
+            match camera.projection() {
+                gltf::camera::Projection::Perspective(p) => {
+                    let mut cam = Camera::matrix_new(&world);
+                    cam.vfov = (p.yfov() as f64).to_degrees();
+                    if let Some(aspect_ratio) = p.aspect_ratio() {
+                        cam.aspect_ratio = aspect_ratio as f64;
+                    }
+                    self.camera = Some(cam);
+                }
+                gltf::camera::Projection::Orthographic(_) => {
+                    eprintln!("WARNING: skipping orthographic glTF camera.");
+                }
+            }
+        }
+        for child in node.children() {
+            self.visit(&child, world)?;
+        }
+        Ok(())
+    }
+
+    fn add_mesh(&mut self, mesh: &gltf::Mesh, world: &Matrix4) -> Result<(), GltfError> {
+        let normal_matrix = match world.inverse() {
+            Some(inverse) => inverse.transpose(),
+            None => return Ok(()),
+        };
+        // A mirroring transform turns counter-clockwise faces clockwise.
+        let flip = world.determinant3() < 0.0;
+        for primitive in mesh.primitives() {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                eprintln!("WARNING: skipping non-triangle glTF primitive.");
+                continue;
+            }
+            let buffers = &self.buffers;
+            let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|b| &b[..]));
+            let positions: Vec<Vector> = match reader.read_positions() {
+                Some(positions) => positions
+                    .map(|p| {
+                        world.transform_point(Vector::new(p[0] as f64, p[1] as f64, p[2] as f64))
+                    })
+                    .collect(),
+                None => continue,
+            };
+            let normals = reader
+                .read_normals()
+                .map(|normals| {
+                    normals
+                        .map(|n| {
+                            normal_matrix
+                                .transform_vector(Vector::new(
+                                    n[0] as f64,
+                                    n[1] as f64,
+                                    n[2] as f64,
+                                ))
+                                .unit()
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            // glTF puts the UV origin at the top left of the image, `ImageTexture`
+            // at the bottom left.
+            let uvs = reader
+                .read_tex_coords(0)
+                .map(|uvs| {
+                    uvs.into_f32()
+                        .map(|t| (t[0] as f64, 1.0 - t[1] as f64))
+                        .collect()
+                })
+                .unwrap_or_default();
+            let colors = reader
+                .read_colors(0)
+                .map(|colors| {
+                    colors
+                        .into_rgb_f32()
+                        .map(|c| Vector::new(c[0] as f64, c[1] as f64, c[2] as f64))
+                        .collect()
+                })
+                .unwrap_or_default();
+            let flat: Vec<u32> = match reader.read_indices() {
+                Some(indices) => indices.into_u32().collect(),
+                None => (0..positions.len() as u32).collect(),
+            };
+            if let Some(i) = flat.iter().find(|&&i| i as usize >= positions.len()) {
+                return Err(GltfError::InvalidData(format!(
+                    "index {} out of range for {} vertices",
+                    i,
+                    positions.len()
+                )));
+            }
+            let indices = flat
+                .chunks_exact(3)
+                .map(|f| {
+                    if flip {
+                        [f[0], f[2], f[1]]
+                    } else {
+                        [f[0], f[1], f[2]]
+                    }
+                })
+                .collect();
+            let material = self.material(&primitive.material());
+            let mut triangle_mesh =
+                TriangleMesh::new(positions, normals, uvs, indices, material.mat.clone());
+            triangle_mesh.colors = colors;
+            triangle_mesh.normal_map = material.normal_map.clone();
+            self.meshes.push((triangle_mesh, material.emissive));
+        }
+        Ok(())
+    }
+
+    fn material(&mut self, material: &gltf::Material) -> Arc<GltfMaterial> {
+        if let Some(cached) = self.materials.get(&material.index()) {
+            return cached.clone();
+        }
+        let pbr = material.pbr_metallic_roughness();
+        let strength = material.emissive_strength().unwrap_or(1.0) as f64;
+        let factor = material.emissive_factor();
+        let emission = Vector::new(factor[0] as f64, factor[1] as f64, factor[2] as f64) * strength;
+        // glTF normal maps follow the OpenGL convention; `scale` is their strength.
+        let normal_map = material.normal_texture().and_then(|t| {
+            let texture = self.texture(t.texture().source().index())?;
+            Some(Arc::new(NormalMap::new(
+                texture,
+                NormalMapConvention::OpenGl,
+                t.scale() as f64,
+            )) as Arc<dyn NormalModifier>)
+        });
+        let emissive = !emission.near_zero();
+        let mat: Arc<dyn Material> = if emissive {
+            match material
+                .emissive_texture()
+                .and_then(|t| self.texture(t.texture().source().index()))
+            {
+                Some(tex) => Arc::new(DiffuseLight::new(Arc::new(ScaledTexture {
+                    tex,
+                    scale: emission,
+                }))),
+                None => Arc::new(DiffuseLight::color_new(emission)),
+            }
+        } else {
+            let base = pbr.base_color_factor();
+            let base_factor = Vector::new(base[0] as f64, base[1] as f64, base[2] as f64);
+            let base_color: Arc<dyn Texture> = match pbr
+                .base_color_texture()
+                .and_then(|t| self.texture(t.texture().source().index()))
+            {
+                Some(tex) => Arc::new(ScaledTexture {
+                    tex,
+                    scale: base_factor,
+                }),
+                None => Arc::new(SolidColor::new(base_factor)),
+            };
+            let metallic_roughness = pbr
+                .metallic_roughness_texture()
+                .and_then(|t| self.texture(t.texture().source().index()));
+            let mat: Arc<dyn Material> = Arc::new(MetallicRoughness::new(
+                base_color,
+                pbr.metallic_factor() as f64,
+                pbr.roughness_factor() as f64,
+                metallic_roughness,
+            ));
+            match material.alpha_mode() {
+                gltf::material::AlphaMode::Blend if base[3] < 1.0 => {
+                    Arc::new(Dissolve::new(mat, base[3] as f64))
+                }
+                gltf::material::AlphaMode::Mask => {
+                    let alpha: Arc<dyn Texture> = match pbr
+                        .base_color_texture()
+                        .and_then(|t| self.image(t.texture().source().index()))
+                    {
+                        Some((_, alpha)) => Arc::new(ScaledTexture {
+                            tex: alpha,
+                            scale: Vector::new(base[3] as f64, base[3] as f64, base[3] as f64),
+                        }),
+                        None => Arc::new(SolidColor::new(Vector::new(
+                            base[3] as f64,
+                            base[3] as f64,
+                            base[3] as f64,
+                        ))),
+                    };
+                    let cutoff = material.alpha_cutoff().unwrap_or(0.5) as f64;
+                    Arc::new(AlphaMask::new(mat, alpha, cutoff))
+                }
+                _ => mat,
+            }
+        };
+        let val = Arc::new(GltfMaterial {
+            mat,
+            normal_map,
+            emissive,
+        });
+        self.materials.insert(material.index(), val.clone());
+        val
+    }
+
+    fn texture(&mut self, image_index: usize) -> Option<Arc<dyn Texture>> {
+        self.image(image_index).map(|(color, _)| color)
+    }
+
+    fn image(&mut self, image_index: usize) -> Option<GltfImage> {
+        if let Some(cached) = self.images.get(&image_index) {
+            return cached.clone();
+        }
+        let image = self.document.images().nth(image_index)?;
+        let val =
+            match gltf::image::Data::from_source(image.source(), Some(&self.dir), &self.buffers) {
+                Ok(data) => {
+                    let (color, alpha) = split_alpha(&data);
+                    let texture = |img: RgbImage| {
+                        Arc::new(ImageTexture {
+                            image: RtwImage {
+                                image_width: data.width as i32,
+                                image_height: data.height as i32,
+                                data: Some(DynamicImage::ImageRgb8(img)),
+                            },
+                        }) as Arc<dyn Texture>
+                    };
+                    Some((texture(color), texture(alpha)))
+                }
+                Err(e) => {
+                    eprintln!("WARNING: could not load glTF image {}: {}", image_index, e);
+                    None
+                }
+            };
+        self.images.insert(image_index, val.clone());
+        val
+    }
+}
+
+// glTF multiplies every texture by its material factor.
+struct ScaledTexture {
+    tex: Arc<dyn Texture>,
+    scale: Vector,
+}
+
+impl Texture for ScaledTexture {
+    fn value(&self, u: f64, v: f64, p: Vector) -> Vector {
+        self.tex.value(u, v, p) * self.scale
+    }
+}
+
+// Converts decoded pixels of any glTF format to 8-bit RGB, gray images spread
+// over all three channels, and an alpha image that is opaque where the format
+// has no alpha.
+fn split_alpha(data: &gltf::image::Data) -> (RgbImage, RgbImage) {
+    use gltf::image::Format;
+    let (channels, size) = match data.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    };
+    let channel = |b: &[u8]| match size {
+        1 => b[0],
+        2 => (u16::from_ne_bytes([b[0], b[1]]) >> 8) as u8,
+        _ => (f32::from_ne_bytes([b[0], b[1], b[2], b[3]]).clamp(0.0, 1.0) * 255.0).round() as u8,
+    };
+    let mut color = Vec::with_capacity(data.pixels.len());
+    let mut alpha = Vec::with_capacity(data.pixels.len());
+    for pixel in data.pixels.chunks_exact(channels * size) {
+        let c: Vec<u8> = pixel.chunks_exact(size).map(channel).collect();
+        let (rgb, a) = match channels {
+            1 => ([c[0]; 3], 255),
+            2 => ([c[0]; 3], c[1]),
+            3 => ([c[0], c[1], c[2]], 255),
+            _ => ([c[0], c[1], c[2]], c[3]),
+        };
+        color.extend_from_slice(&rgb);
+        alpha.extend_from_slice(&[a; 3]);
+    }
+    let image = |pixels| {
+        RgbImage::from_raw(data.width, data.height, pixels)
+            .unwrap_or_else(|| RgbImage::new(data.width, data.height))
+    };
+    (image(color), image(alpha))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interval::Interval;
+    use crate::material::ScatterRecord;
+    use crate::ray::Ray;
+    use crate::rtweekend::INFINITY;
+
+    // One triangle in the z = 0 plane, drawn as is with a cut-out material and
+    // moved along +x with an opaque one. The buffer's file name needs
+    // percent-decoding.
+    const SCENE: &str = r#"{
+        "asset": { "version": "2.0" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0, 1] }],
+        "nodes": [
+            { "mesh": 0 },
+            { "mesh": 1, "translation": [5.0, 0.0, 0.0] }
+        ],
+        "meshes": [
+            { "primitives": [{ "attributes": { "POSITION": 0 }, "material": 0 }] },
+            { "primitives": [{ "attributes": { "POSITION": 0 }, "material": 1 }] }
+        ],
+        "materials": [
+            {
+                "pbrMetallicRoughness": { "baseColorFactor": [1.0, 1.0, 1.0, 0.25] },
+                "alphaMode": "MASK",
+                "alphaCutoff": 0.5
+            },
+            {
+                "pbrMetallicRoughness": { "baseColorFactor": [1.0, 1.0, 1.0, 0.25] }
+            }
+        ],
+        "accessors": [{
+            "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+            "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0]
+        }],
+        "bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+        "buffers": [{ "uri": "triangle%20data.bin", "byteLength": 36 }]
+    }"#;
+
+    fn write_scene(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut data = Vec::new();
+        for x in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+            data.extend_from_slice(&x.to_le_bytes());
+        }
+        std::fs::write(dir.join("triangle data.bin"), data).unwrap();
+        std::fs::write(dir.join("scene.gltf"), SCENE).unwrap();
+        dir.join("scene.gltf")
+    }
+
+    // Whether the material at the hit lets the ray through unchanged.
+    fn passes_through(scene: &GltfScene, x: f64) -> bool {
+        let r = Ray::new(
+            Vector::new(x + 0.25, 0.25, 1.0),
+            Vector::new(0.0, 0.0, -1.0),
+            0.0,
+        );
+        let rec = scene
+            .world
+            .hit(&r, &Interval::new(0.001, INFINITY))
+            .unwrap();
+        (0..32).all(|_| {
+            let mut srec: ScatterRecord = Default::default();
+            rec.mat.as_ref().unwrap().scatter(&r, &rec, &mut srec)
+                && srec.skip_pdf
+                && srec.skip_pdf_ray.direction.z == -1.0
+                && srec.skip_pdf_ray.direction.x == 0.0
+        })
+    }
+
+    #[test]
+    fn mask_cuts_out_below_the_cutoff_and_opaque_ignores_alpha() {
+        let path = write_scene("raytracer_gltf_mask");
+        let scene = load_gltf(&path).unwrap();
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        assert!(passes_through(&scene, 0.0));
+        assert!(!passes_through(&scene, 5.0));
+        assert!(scene.lights.objects.is_empty() && scene.camera.is_none());
+    }
+
+    #[test]
+    fn missing_buffer_is_an_error() {
+        let path = write_scene("raytracer_gltf_missing");
+        std::fs::remove_file(path.parent().unwrap().join("triangle data.bin")).unwrap();
+        let result = load_gltf(&path);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        assert!(matches!(result, Err(GltfError::Gltf(_))));
+    }
+
+    #[test]
+    fn images_split_into_color_and_alpha() {
+        let gray_alpha = gltf::image::Data {
+            pixels: vec![10, 20, 30, 40],
+            format: gltf::image::Format::R8G8,
+            width: 2,
+            height: 1,
+        };
+        let (color, alpha) = split_alpha(&gray_alpha);
+        assert_eq!(color.into_raw(), vec![10, 10, 10, 30, 30, 30]);
+        assert_eq!(alpha.into_raw(), vec![20, 20, 20, 40, 40, 40]);
+        let rgb = gltf::image::Data {
+            pixels: vec![1, 2, 3],
+            format: gltf::image::Format::R8G8B8,
+            width: 1,
+            height: 1,
+        };
+        let (color, alpha) = split_alpha(&rgb);
+        assert_eq!(color.into_raw(), vec![1, 2, 3]);
+        assert_eq!(alpha.into_raw(), vec![255, 255, 255]);
+    }
+}
@@ -0,0 +1,352 @@
+use crate::aabb::AABB;
+use crate::hittable::{HitRecord, Hittable};
+use crate::image::RtwImage;
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::rtweekend::rgb_to_gray;
+use crate::triangle::intersect_watertight;
+use crate::vec3::Vector;
+
+use image::Rgb;
+use std::sync::Arc;
+
+// A terrain of `width` by `depth` height samples spread over `extent.x` by
+// `extent.z` from `corner`, a sample of h standing at corner.y + h * extent.y.
+// Every grid cell is two triangles with smooth normals, but none are stored:
+// rays walk a min-max quadtree over the cells front to back and only test the
+// triangles of the cells whose height range they actually pass through. The
+// constructors report and return None for fewer than 2x2 samples.
+pub struct Heightfield {
+    pub heights: Vec<f64>,
+    pub width: usize,
+    pub depth: usize,
+    pub corner: Vector,
+    pub extent: Vector,
+    pub mat: Arc<dyn Material>,
+    // levels[0] holds the height range of every cell, each further level that
+    // of 2x2 blocks of the one below, up to a single root node.
+    levels: Vec<Vec<(f64, f64)>>,
+    normals: Vec<Vector>,
+    bbox: AABB,
+}
+
+impl Heightfield {
+    pub fn new(
+        heights: Vec<f64>,
+        width: usize,
+        depth: usize,
+        corner: Vector,
+        extent: Vector,
+        mat: Arc<dyn Material>,
+    ) -> Option<Self> {
+        if width < 2 || depth < 2 {
+            eprintln!(
+                "ERROR: Heightfield needs at least 2x2 samples, got {}x{}.",
+                width, depth
+            );
+            return None;
+        }
+        if heights.len() != width * depth {
+            eprintln!(
+                "ERROR: Heightfield of {}x{} samples was given {} heights.",
+                width,
+                depth,
+                heights.len()
+            );
+            return None;
+        }
+        let mut val = Self {
+            heights,
+            width,
+            depth,
+            corner,
+            extent,
+            mat,
+            levels: Vec::new(),
+            normals: Vec::new(),
+            bbox: AABB::default(),
+        };
+        val.build_levels();
+        val.build_normals();
+        let (low, high) = val.levels.last().unwrap()[0];
+        val.bbox = AABB::point_new(
+            &Vector::new(corner.x, low, corner.z),
+            &Vector::new(corner.x + extent.x, high, corner.z + extent.z),
+        );
+        Some(val)
+    }
+    // Gray levels of the image as heights in [0, 1]. The image is laid out the way
+    // `ImageTexture` maps it, so the same image also textures the terrain. None,
+    // after reporting the file, if it cannot be loaded or is below 2x2 pixels.
+    pub fn image_new(
+        image_filename: &str,
+        corner: Vector,
+        extent: Vector,
+        mat: Arc<dyn Material>,
+    ) -> Option<Self> {
+        let image = RtwImage::new(image_filename);
+        // `RtwImage::new` has already reported a file it could not load.
+        image.data.as_ref()?;
+        let width = image.image_width as usize;
+        let depth = image.image_height as usize;
+        if width < 2 || depth < 2 {
+            eprintln!(
+                "ERROR: Heightfield image '{}' is smaller than 2x2 pixels.",
+                image_filename
+            );
+            return None;
+        }
+        let mut heights = Vec::with_capacity(width * depth);
+        for k in 0..depth {
+            for i in 0..width {
+                let pixel = image.pixel_data(i as i32, (depth - 1 - k) as i32);
+                heights.push(rgb_to_gray(&Rgb(pixel)) / 255.0);
+            }
+        }
+        Self::new(heights, width, depth, corner, extent, mat)
+    }
+    // Samples `height(u, v)` with u and v in [0, 1] across the extent, for
+    // example `|u, v| perlin.turb(Vector::new(8.0 * u, 0.0, 8.0 * v), 7)`.
+    pub fn function_new<F: Fn(f64, f64) -> f64>(
+        height: F,
+        width: usize,
+        depth: usize,
+        corner: Vector,
+        extent: Vector,
+        mat: Arc<dyn Material>,
+    ) -> Option<Self> {
+        let mut heights = Vec::with_capacity(width * depth);
+        for k in 0..depth {
+            for i in 0..width {
+                // Kept finite for a single row or column, which `new` then rejects.
+                heights.push(height(
+                    i as f64 / width.saturating_sub(1).max(1) as f64,
+                    k as f64 / depth.saturating_sub(1).max(1) as f64,
+                ));
+            }
+        }
+        Self::new(heights, width, depth, corner, extent, mat)
+    }
+    fn point(&self, i: usize, k: usize) -> Vector {
+        Vector::new(
+            self.corner.x + self.extent.x * i as f64 / (self.width - 1) as f64,
+            self.corner.y + self.extent.y * self.heights[k * self.width + i],
+            self.corner.z + self.extent.z * k as f64 / (self.depth - 1) as f64,
+        )
+    }
+    fn level_size(&self, level: usize) -> (usize, usize) {
+        let cells = |n: usize| ((n - 1) + (1 << level) - 1) >> level;
+        (cells(self.width), cells(self.depth))
+    }
+    fn build_levels(&mut self) {
+        let (cells_x, cells_z) = self.level_size(0);
+        let mut cells = Vec::with_capacity(cells_x * cells_z);
+        for k in 0..cells_z {
+            for i in 0..cells_x {
+                let ys = [
+                    self.point(i, k).y,
+                    self.point(i + 1, k).y,
+                    self.point(i, k + 1).y,
+                    self.point(i + 1, k + 1).y,
+                ];
+                let low = ys.iter().cloned().fold(f64::INFINITY, f64::min);
+                let high = ys.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+                cells.push((low, high));
+            }
+        }
+        self.levels.push(cells);
+        while self.level_size(self.levels.len() - 1) != (1, 1) {
+            let level = self.levels.len();
+            let (below_x, below_z) = self.level_size(level - 1);
+            let (size_x, size_z) = self.level_size(level);
+            let below = &self.levels[level - 1];
+            let mut nodes = Vec::with_capacity(size_x * size_z);
+            for k in 0..size_z {
+                for i in 0..size_x {
+                    let mut range = (f64::INFINITY, f64::NEG_INFINITY);
+                    for (ci, ck) in [
+                        (2 * i, 2 * k),
+                        (2 * i + 1, 2 * k),
+                        (2 * i, 2 * k + 1),
+                        (2 * i + 1, 2 * k + 1),
+                    ] {
+                        if ci < below_x && ck < below_z {
+                            let (low, high) = below[ck * below_x + ci];
+                            range = (range.0.min(low), range.1.max(high));
+                        }
+                    }
+                    nodes.push(range);
+                }
+            }
+            self.levels.push(nodes);
+        }
+    }
+    // Central differences of the scaled heights, one-sided along the border.
+    fn build_normals(&mut self) {
+        let dx = self.extent.x / (self.width - 1) as f64;
+        let dz = self.extent.z / (self.depth - 1) as f64;
+        for k in 0..self.depth {
+            for i in 0..self.width {
+                let (i0, i1) = (i.saturating_sub(1), (i + 1).min(self.width - 1));
+                let (k0, k1) = (k.saturating_sub(1), (k + 1).min(self.depth - 1));
+                let slope_x = (self.point(i1, k).y - self.point(i0, k).y) / ((i1 - i0) as f64 * dx);
+                let slope_z = (self.point(i, k1).y - self.point(i, k0).y) / ((k1 - k0) as f64 * dz);
+                self.normals
+                    .push(Vector::new(-slope_x, 1.0, -slope_z).unit());
+            }
+        }
+    }
+    fn node_box(&self, level: usize, i: usize, k: usize) -> AABB {
+        let (size_x, _) = self.level_size(level);
+        let (low, high) = self.levels[level][k * size_x + i];
+        let span = 1 << level;
+        let corner = |i: usize, k: usize| {
+            let p = self.point(i.min(self.width - 1), k.min(self.depth - 1));
+            Vector::new(p.x, 0.0, p.z)
+        };
+        let a = corner(i * span, k * span);
+        let b = corner((i + 1) * span, (k + 1) * span);
+        AABB::point_new(&Vector::new(a.x, low, a.z), &Vector::new(b.x, high, b.z))
+    }
+    fn traverse(
+        &self,
+        r: &Ray,
+        ray_t: &Interval,
+        level: usize,
+        i: usize,
+        k: usize,
+        best: &mut Option<HitRecord>,
+    ) {
+        let t_max = best.as_ref().map_or(ray_t.max, |rec| rec.t);
+        if self
+            .node_box(level, i, k)
+            .hit_interval(r, &Interval::new(ray_t.min, t_max))
+            .is_none()
+        {
+            return;
+        }
+        if level == 0 {
+            self.hit_cell(r, &Interval::new(ray_t.min, t_max), i, k, best);
+            return;
+        }
+        let (size_x, size_z) = self.level_size(level - 1);
+        let flip_x = usize::from(r.direction.x < 0.0);
+        let flip_z = usize::from(r.direction.z < 0.0);
+        for (a, b) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let ci = 2 * i + (a ^ flip_x);
+            let ck = 2 * k + (b ^ flip_z);
+            if ci < size_x && ck < size_z {
+                self.traverse(r, ray_t, level - 1, ci, ck, best);
+            }
+        }
+    }
+    fn hit_cell(
+        &self,
+        r: &Ray,
+        ray_t: &Interval,
+        i: usize,
+        k: usize,
+        best: &mut Option<HitRecord>,
+    ) {
+        let corners = [(i, k), (i + 1, k + 1), (i + 1, k), (i, k + 1)];
+        // Wound so the geometric normals point up.
+        for triangle in [
+            [corners[0], corners[1], corners[2]],
+            [corners[0], corners[3], corners[1]],
+        ] {
+            let t_max = best.as_ref().map_or(ray_t.max, |rec| rec.t);
+            let points = triangle.map(|(i, k)| self.point(i, k));
+            if let Some((t, weights)) =
+                intersect_watertight(r, &points, &Interval::new(ray_t.min, t_max))
+            {
+                let mut normal = Vector::default();
+                for (w, &(i, k)) in weights.iter().zip(triangle.iter()) {
+                    normal = normal + self.normals[k * self.width + i] * *w;
+                }
+                let mut rec = HitRecord::new(r.at(t), Vector::default(), t, false);
+                rec.set_face_normal(r, &normal.unit());
+                rec.u = (rec.p.x - self.corner.x) / self.extent.x;
+                rec.v = (rec.p.z - self.corner.z) / self.extent.z;
+                // u runs along x and v along z, climbing the facet's slope.
+                let facet = (points[1] - points[0]).cross(&(points[2] - points[0]));
+                rec.dpdu = Vector::new(1.0, -facet.x / facet.y, 0.0) * self.extent.x;
+                rec.dpdv = Vector::new(0.0, -facet.z / facet.y, 1.0) * self.extent.z;
+                rec.mat = Some(self.mat.clone());
+                *best = Some(rec);
+            }
+        }
+    }
+}
+
+impl Hittable for Heightfield {
+    fn hit(&self, r: &Ray, ray_t: &Interval) -> Option<HitRecord> {
+        let mut best = None;
+        self.traverse(r, ray_t, self.levels.len() - 1, 0, 0, &mut best);
+        best
+    }
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::rtweekend::INFINITY;
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn quadtree_finds_the_same_hits_as_every_cell() {
+        // Sizes that are not powers of two leave partial nodes along two edges.
+        let terrain = Heightfield::function_new(
+            |u, v| ((7.0 * u).sin() * (5.0 * v).cos() + 1.0) * 0.5,
+            11,
+            6,
+            Vector::new(-2.0, -0.5, 1.0),
+            Vector::new(5.0, 1.5, 3.0),
+            material(),
+        )
+        .unwrap();
+        let ray_t = Interval::new(0.001, INFINITY);
+        let mut hits = 0;
+        for n in 0..500 {
+            let x = -2.5 + (n % 25) as f64 * 0.25;
+            let z = 0.5 + (n / 25) as f64 * 0.2;
+            let r = Ray::new(
+                Vector::new(x, 3.0, z),
+                Vector::new(0.7 - (n % 7) as f64 * 0.2, -1.0, 0.3 - (n % 3) as f64 * 0.3),
+                0.0,
+            );
+            let mut every_cell = None;
+            for k in 0..terrain.depth - 1 {
+                for i in 0..terrain.width - 1 {
+                    terrain.hit_cell(&r, &ray_t, i, k, &mut every_cell);
+                }
+            }
+            match (terrain.hit(&r, &ray_t), every_cell) {
+                (None, None) => {}
+                (Some(a), Some(b)) => {
+                    assert!((a.t - b.t).abs() < 1e-12, "ray {}", n);
+                    hits += 1;
+                }
+                _ => panic!("ray {} hit only one of the two", n),
+            }
+        }
+        assert!(hits > 100, "{}", hits);
+    }
+
+    #[test]
+    fn fewer_than_two_by_two_samples_are_rejected() {
+        let corner = Vector::new(0.0, 0.0, 0.0);
+        let extent = Vector::new(1.0, 1.0, 1.0);
+        assert!(Heightfield::new(vec![0.0; 3], 3, 1, corner, extent, material()).is_none());
+        assert!(Heightfield::new(vec![0.0; 3], 2, 2, corner, extent, material()).is_none());
+        assert!(Heightfield::function_new(|_, _| 0.0, 1, 5, corner, extent, material()).is_none());
+        assert!(Heightfield::new(vec![0.0; 4], 2, 2, corner, extent, material()).is_some());
+    }
+}
@@ -1,6 +1,7 @@
 use crate::aabb::AABB;
 use crate::interval::Interval;
 use crate::material::Material;
+use crate::matrix::Matrix4;
 use crate::ray::Ray;
 use crate::rtweekend::{degrees_to_radians, INFINITY};
 use crate::vec3::Vector;
@@ -15,6 +16,12 @@ pub struct HitRecord {
     pub u: f64,
     pub v: f64,
     pub front_face: bool,
+    // Interpolated vertex color, multiplied into the albedo of diffuse materials.
+    pub color: Option<Vector>,
+    // Derivatives of the hit point along u and v, zero where a primitive does
+    // not provide them. Normal and bump maps build their tangent frame on these.
+    pub dpdu: Vector,
+    pub dpdv: Vector,
 }
 impl HitRecord {
     pub fn new(a: Vector, b: Vector, c: f64, d: bool) -> Self {
@@ -26,6 +33,15 @@ impl HitRecord {
             u: 0.0,
             v: 0.0,
             front_face: d,
+            color: None,
+            dpdu: Vector::default(),
+            dpdv: Vector::default(),
+        }
+    }
+    pub fn albedo(&self, base: Vector) -> Vector {
+        match self.color {
+            Some(color) => base * color,
+            None => base,
         }
     }
     pub fn set_face_normal(&mut self, r: &Ray, outward_normal: &Vector) {
@@ -42,6 +58,9 @@ impl HitRecord {
     }
 }
 
+// Bounds the crossings `Hittable::hit_all` collects along one ray.
+const MAX_CROSSINGS: usize = 1024;
+
 pub trait Hittable: Send + Sync {
     fn hit(&self, r: &Ray, ray_t: &Interval) -> Option<HitRecord>;
     fn bounding_box(&self) -> AABB;
@@ -51,6 +70,79 @@ pub trait Hittable: Send + Sync {
     fn random(&self, _origin: Vector) -> Vector {
         Vector::new(1.0, 0.0, 0.0)
     }
+    // Every surface crossing within `ray_t` in order of t, so callers can tell
+    // where the ray enters (`front_face`) and leaves a closed object. Each search
+    // resumes a step past the last hit that grows with t, as a fixed one is lost
+    // to rounding far away, and at most `MAX_CROSSINGS` are reported.
+    fn hit_all(&self, r: &Ray, ray_t: &Interval) -> Vec<HitRecord> {
+        let mut crossings: Vec<HitRecord> = Vec::new();
+        let mut t_min = ray_t.min;
+        while crossings.len() < MAX_CROSSINGS {
+            let rec = match self.hit(r, &Interval::new(t_min, ray_t.max)) {
+                Some(rec) => rec,
+                None => break,
+            };
+            t_min = rec.t + 1e-6 * rec.t.abs().max(1.0);
+            crossings.push(rec);
+        }
+        crossings
+    }
+}
+
+// Solid-angle pdf of sampling a point uniformly over `area` of `object`. Every
+// surface point along the direction could have been the sampled one, so the
+// densities distance^2 / (cosine * area) of all hits are summed.
+pub fn area_pdf_value(object: &dyn Hittable, area: f64, origin: Vector, direction: Vector) -> f64 {
+    let r = Ray::new(origin, direction, 0.0);
+    object
+        .hit_all(&r, &Interval::new(0.001, INFINITY))
+        .iter()
+        .map(|rec| {
+            let distance_squared = rec.t * rec.t * direction.length_square();
+            let cosine = (direction.dot(&rec.normal) / direction.length()).abs();
+            distance_squared / (cosine * area)
+        })
+        .sum()
+}
+
+// Test check for light sampling: `pdf_value` seen from `origin` integrates to
+// one over a Fibonacci lattice of directions, and the directions of `random`
+// are where it is nonzero, with a mean 1 / pdf equal to the solid angle it covers.
+#[cfg(test)]
+pub fn assert_pdf_matches_random(object: &dyn Hittable, origin: Vector) {
+    let n = 200_000;
+    let golden_angle = std::f64::consts::PI * (3.0 - 5.0f64.sqrt());
+    let (mut integral, mut covered) = (0.0, 0.0);
+    for i in 0..n {
+        let z = 1.0 - (2 * i + 1) as f64 / n as f64;
+        let phi = golden_angle * i as f64;
+        let rho = (1.0 - z * z).sqrt();
+        let pdf = object.pdf_value(origin, Vector::new(rho * phi.cos(), rho * phi.sin(), z));
+        integral += pdf;
+        covered += if pdf > 0.0 { 1.0 } else { 0.0 };
+    }
+    let cell = 4.0 * std::f64::consts::PI / n as f64;
+    let (integral, covered) = (integral * cell, covered * cell);
+    assert!(
+        (integral - 1.0).abs() < 0.02,
+        "pdf integrates to {}",
+        integral
+    );
+
+    let samples = 20_000;
+    let mut solid_angle = 0.0;
+    for _ in 0..samples {
+        let pdf = object.pdf_value(origin, object.random(origin));
+        assert!(pdf > 0.0, "sampled a direction of zero density");
+        solid_angle += 1.0 / pdf;
+    }
+    solid_angle /= samples as f64;
+    assert!(
+        (solid_angle / covered - 1.0).abs() < 0.05,
+        "{} != {}",
+        solid_angle,
+        covered
+    );
 }
 
 pub struct Translate {
@@ -84,6 +176,12 @@ impl Hittable for Translate {
     fn bounding_box(&self) -> AABB {
         self.bbox
     }
+    fn pdf_value(&self, origin: Vector, direction: Vector) -> f64 {
+        self.object.pdf_value(origin - self.offset, direction)
+    }
+    fn random(&self, origin: Vector) -> Vector {
+        self.object.random(origin - self.offset)
+    }
 }
 
 pub struct RotateY {
@@ -130,29 +228,34 @@ impl RotateY {
         val.bbox = AABB::point_new(&min, &max);
         val
     }
+    fn to_object(&self, v: Vector) -> Vector {
+        Vector::new(
+            self.cos_theta * v.x - self.sin_theta * v.z,
+            v.y,
+            self.sin_theta * v.x + self.cos_theta * v.z,
+        )
+    }
+    fn to_world(&self, v: Vector) -> Vector {
+        Vector::new(
+            self.cos_theta * v.x + self.sin_theta * v.z,
+            v.y,
+            -self.sin_theta * v.x + self.cos_theta * v.z,
+        )
+    }
 }
 
 impl Hittable for RotateY {
     fn hit(&self, r: &Ray, ray_t: &Interval) -> Option<HitRecord> {
-        let mut origin = r.origin;
-        let mut direction = r.direction;
-
-        origin.x = self.cos_theta * r.origin.x - self.sin_theta * r.origin.z;
-        origin.z = self.sin_theta * r.origin.x + self.cos_theta * r.origin.z;
-
-        direction.x = self.cos_theta * r.direction.x - self.sin_theta * r.direction.z;
-        direction.z = self.sin_theta * r.direction.x + self.cos_theta * r.direction.z;
-
-        let rotated_r = Ray::new(origin, direction, r.time);
+        let rotated_r = Ray::new(
+            self.to_object(r.origin),
+            self.to_object(r.direction),
+            r.time,
+        );
         if let Some(mut rec) = self.object.hit(&rotated_r, ray_t) {
-            let mut p = rec.p;
-            p.x = self.cos_theta * rec.p.x + self.sin_theta * rec.p.z;
-            p.z = -self.sin_theta * rec.p.x + self.cos_theta * rec.p.z;
-            let mut normal = rec.normal;
-            normal.x = self.cos_theta * rec.normal.x + self.sin_theta * rec.normal.z;
-            normal.z = -self.sin_theta * rec.normal.x + self.cos_theta * rec.normal.z;
-            rec.p = p;
-            rec.normal = normal;
+            rec.p = self.to_world(rec.p);
+            rec.normal = self.to_world(rec.normal);
+            rec.dpdu = self.to_world(rec.dpdu);
+            rec.dpdv = self.to_world(rec.dpdv);
             Some(rec)
         } else {
             None
@@ -161,4 +264,104 @@ impl Hittable for RotateY {
     fn bounding_box(&self) -> AABB {
         self.bbox
     }
+    fn pdf_value(&self, origin: Vector, direction: Vector) -> f64 {
+        self.object
+            .pdf_value(self.to_object(origin), self.to_object(direction))
+    }
+    fn random(&self, origin: Vector) -> Vector {
+        self.to_world(self.object.random(self.to_object(origin)))
+    }
+}
+
+pub struct Transform {
+    object: Arc<dyn Hittable>,
+    matrix: Matrix4,
+    inverse: Matrix4,
+    normal_matrix: Matrix4,
+    bbox: AABB,
+}
+
+// The constructors return None for a singular matrix, such as a zero scale,
+// which flattens the object and cannot be traced through.
+impl Transform {
+    pub fn new(obj: Arc<dyn Hittable>, m: Matrix4) -> Option<Self> {
+        let inverse = m.inverse()?;
+        let bbox = m.transform_box(&obj.bounding_box());
+        Some(Self {
+            object: obj,
+            matrix: m,
+            inverse,
+            normal_matrix: inverse.transpose(),
+            bbox,
+        })
+    }
+    pub fn translate(obj: Arc<dyn Hittable>, offset: &Vector) -> Option<Self> {
+        Self::new(obj, Matrix4::translation(*offset))
+    }
+    pub fn rotate(obj: Arc<dyn Hittable>, axis: &Vector, angle: f64) -> Option<Self> {
+        Self::new(obj, Matrix4::rotation(*axis, angle))
+    }
+    pub fn scale(obj: Arc<dyn Hittable>, factors: &Vector) -> Option<Self> {
+        Self::new(obj, Matrix4::scaling(*factors))
+    }
+    pub fn matrix(&self) -> Matrix4 {
+        self.matrix
+    }
+}
+
+impl Hittable for Transform {
+    fn hit(&self, r: &Ray, ray_t: &Interval) -> Option<HitRecord> {
+        // Affine maps keep the ray parameter, so t needs no rescaling.
+        let object_r = Ray::new(
+            self.inverse.transform_point(r.origin),
+            self.inverse.transform_vector(r.direction),
+            r.time,
+        );
+        let mut rec = self.object.hit(&object_r, ray_t)?;
+        rec.p = self.matrix.transform_point(rec.p);
+        rec.normal = self.normal_matrix.transform_vector(rec.normal).unit();
+        rec.dpdu = self.matrix.transform_vector(rec.dpdu);
+        rec.dpdv = self.matrix.transform_vector(rec.dpdv);
+        Some(rec)
+    }
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+    // Directions map linearly into object space; the object's solid-angle pdf is
+    // scaled by the Jacobian |det A| / |A w|^3 of that map on the unit sphere, which
+    // keeps scaled and sheared lights sampled correctly.
+    fn pdf_value(&self, origin: Vector, direction: Vector) -> f64 {
+        let object_direction = self.inverse.transform_vector(direction.unit());
+        let length = object_direction.length();
+        let object_pdf = self
+            .object
+            .pdf_value(self.inverse.transform_point(origin), object_direction);
+        object_pdf * self.inverse.determinant3().abs() / (length * length * length)
+    }
+    fn random(&self, origin: Vector) -> Vector {
+        self.matrix
+            .transform_vector(self.object.random(self.inverse.transform_point(origin)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::quad::Quad;
+
+    #[test]
+    fn hit_all_terminates_far_away() {
+        let far = 1e12;
+        let quad = Quad::new(
+            Vector::new(0.0, 0.0, -far),
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+            Arc::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5))),
+        );
+        let r = Ray::new(Vector::new(0.5, 0.5, 0.0), Vector::new(0.0, 0.0, -1.0), 0.0);
+        let crossings = quad.hit_all(&r, &Interval::new(0.001, INFINITY));
+        assert_eq!(crossings.len(), 1);
+        assert!((crossings[0].t - far).abs() < 1.0);
+    }
 }
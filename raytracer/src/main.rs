@@ -1,28 +1,50 @@
 use std::process::exit;
 use std::sync::Arc;
 pub mod aabb;
+pub mod animation;
 pub mod bvh;
 pub mod camera;
 pub mod canny;
+pub mod cone;
 pub mod constant_medium;
+pub mod csg;
+pub mod curve;
+pub mod cylinder;
+pub mod disk;
+pub mod displacement;
+pub mod gltf_import;
+pub mod heightfield;
 pub mod hittable;
 pub mod hittable_list;
 pub mod image;
 pub mod interval;
 pub mod material;
+pub mod matrix;
+pub mod mesh;
+pub mod motion;
 pub mod normal_mapping;
 pub mod obj;
 pub mod onb;
+pub mod paraboloid;
 pub mod pdf;
 pub mod perlin;
+pub mod ply;
+pub mod preset;
 pub mod quad;
+pub mod quartic;
 pub mod ray;
 pub mod rtweekend;
+pub mod sdf;
 pub mod sphere;
+pub mod stereo;
+pub mod stl;
+pub mod subdivision;
 pub mod texture;
+pub mod torus;
 pub mod triangle;
 pub mod vec3;
 
+use animation::{CameraAnimation, CameraKeyframe};
 use bvh::BvhNode;
 use hittable::{RotateY, Translate};
 use normal_mapping::NormalMapping;
@@ -277,21 +299,21 @@ fn cornell_box() {
         Vector::new(0.0, 0.0, 105.0),
         light,
     )));
-    world.add(Arc::new(NormalMapping::new(
+    world.add(Arc::new(NormalMapping::quad_new(
         "normalmapping2.jpg",
         Vector::new(0.0, 555.0, 0.0),
         Vector::new(555.0, 0.0, 0.0),
         Vector::new(0.0, 0.0, 555.0),
         white.clone(),
     )));
-    world.add(Arc::new(NormalMapping::new(
+    world.add(Arc::new(NormalMapping::quad_new(
         "normalmapping2.jpg",
         Vector::new(0.0, 0.0, 555.0),
         Vector::new(555.0, 0.0, 0.0),
         Vector::new(0.0, 0.0, -555.0),
         white.clone(),
     )));
-    world.add(Arc::new(NormalMapping::new(
+    world.add(Arc::new(NormalMapping::quad_new(
         "normalmapping2.jpg",
         Vector::new(555.0, 0.0, 555.0),
         Vector::new(-555.0, 0.0, 0.0),
@@ -585,21 +607,21 @@ fn test_obj() {
         Vector::new(0.0, 0.0, 105.0),
         light,
     )));
-    world.add(Arc::new(NormalMapping::new(
+    world.add(Arc::new(NormalMapping::quad_new(
         "normalmapping3.jpg",
         Vector::new(0.0, 555.0, 0.0),
         Vector::new(555.0, 0.0, 0.0),
         Vector::new(0.0, 0.0, 555.0),
         white.clone(),
     )));
-    world.add(Arc::new(NormalMapping::new(
+    world.add(Arc::new(NormalMapping::quad_new(
         "normalmapping3.jpg",
         Vector::new(0.0, 0.0, 555.0),
         Vector::new(555.0, 0.0, 0.0),
         Vector::new(0.0, 0.0, -555.0),
         white.clone(),
     )));
-    world.add(Arc::new(NormalMapping::new(
+    world.add(Arc::new(NormalMapping::quad_new(
         "normalmapping3.jpg",
         Vector::new(555.0, 0.0, 555.0),
         Vector::new(-555.0, 0.0, 0.0),
@@ -616,6 +638,8 @@ fn test_obj() {
     let mut cam: Camera = Default::default();
     cam.render(world, Arc::new(lights));
 }
+// Kept as first written; the clones of the last uses are harmless.
+#[allow(clippy::redundant_clone)]
 fn final_scene() {
     let mut world: HittableList = Default::default();
     let mut lights: HittableList = Default::default();
@@ -623,7 +647,7 @@ fn final_scene() {
     let light = Arc::new(DiffuseLight::color_new(Vector::new(20.0, 20.0, 20.0)));
 
     //left
-    world.add(Arc::new(NormalMapping::new(
+    world.add(Arc::new(NormalMapping::quad_new(
         "normalmapping3.jpg",
         Vector::new(1600.0, 0.0, 0.0),
         Vector::new(0.0, 0.0, 1600.0),
@@ -631,7 +655,7 @@ fn final_scene() {
         white.clone(),
     )));
     //right
-    world.add(Arc::new(NormalMapping::new(
+    world.add(Arc::new(NormalMapping::quad_new(
         "normalmapping3.jpg",
         Vector::new(0.0, 0.0, 1600.0),
         Vector::new(0.0, 0.0, -1600.0),
@@ -713,6 +737,72 @@ fn final_scene() {
     let mut cam: Camera = Default::default();
     cam.render(world, Arc::new(lights));
 }
+fn camera_flythrough() {
+    let mut world: HittableList = Default::default();
+    let mut lights: HittableList = Default::default();
+    let checker = Arc::new(CheckerTexture::color_new(
+        0.32,
+        Vector::new(0.2, 0.3, 0.1),
+        Vector::new(0.9, 0.9, 0.9),
+    ));
+    world.add(Arc::new(Sphere::new(
+        Vector::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(Lambertian::arc_new(checker)),
+    )));
+    world.add(Arc::new(Sphere::new_moving(
+        Vector::new(-3.0, 1.0, 0.0),
+        Vector::new(3.0, 1.0, 0.0),
+        1.0,
+        Arc::new(Lambertian::new(Vector::new(0.7, 0.3, 0.1))),
+    )));
+    world.add(Arc::new(Sphere::new(
+        Vector::new(0.0, 1.0, -3.0),
+        1.0,
+        Arc::new(Metal::new(Vector::new(0.7, 0.6, 0.5), 0.0)),
+    )));
+    let light = Arc::new(DiffuseLight::color_new(Vector::new(10.0, 10.0, 10.0)));
+    world.add(Arc::new(Sphere::new(
+        Vector::new(0.0, 8.0, 0.0),
+        2.0,
+        light.clone(),
+    )));
+    lights.add(Arc::new(Sphere::new(
+        Vector::new(0.0, 8.0, 0.0),
+        2.0,
+        light,
+    )));
+
+    let mut animation = CameraAnimation::new(48, 0.5);
+    animation.add_keyframe(CameraKeyframe::new(
+        0.0,
+        Vector::new(13.0, 2.0, 3.0),
+        Vector::new(0.0, 1.0, 0.0),
+        30.0,
+        10.0,
+    ));
+    animation.add_keyframe(CameraKeyframe::new(
+        0.5,
+        Vector::new(3.0, 4.0, 13.0),
+        Vector::new(0.0, 1.0, 0.0),
+        40.0,
+        13.0,
+    ));
+    animation.add_keyframe(CameraKeyframe::new(
+        1.0,
+        Vector::new(-13.0, 2.0, 3.0),
+        Vector::new(0.0, 1.0, 0.0),
+        30.0,
+        10.0,
+    ));
+    let cam = Camera {
+        image_width: 400,
+        samples_per_pixel: 100,
+        background: Vector::new(0.7, 0.8, 1.0),
+        ..Default::default()
+    };
+    animation.render(&cam, world, Arc::new(lights), "output/flythrough");
+}
 fn main() {
     let f = random_double_range(0.0, 1.0);
     if f < 0.000001 {
@@ -727,6 +817,7 @@ fn main() {
         test_triangle();
         book2_final_scene();
         test_obj();
+        camera_flythrough();
     } else {
         final_scene();
     }
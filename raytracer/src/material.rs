@@ -54,7 +54,7 @@ impl Material for Lambertian {
         // *attenuation = self.tex.value(rec.u, rec.v, rec.p);
         // *pdf = uvw.axis[2].dot(&scattered.direction) / PI;
 
-        srec.attenuation = self.tex.value(rec.u, rec.v, rec.p);
+        srec.attenuation = rec.albedo(self.tex.value(rec.u, rec.v, rec.p));
         srec.pdf_ptr = Some(Arc::new(CosinePdf::new(rec.normal)));
         srec.skip_pdf = false;
         true
@@ -96,6 +96,125 @@ impl Material for Metal {
     }
 }
 
+// Phong-style MTL material: a mirror lobe blurred by `fuzz` is picked with
+// probability max(Ks), the remaining energy goes to the diffuse lobe.
+pub struct Glossy {
+    diffuse: Arc<dyn Texture>,
+    specular: Vector,
+    fuzz: f64,
+}
+
+impl Glossy {
+    pub fn new(diffuse: Arc<dyn Texture>, specular: Vector, fuzz: f64) -> Self {
+        Self {
+            diffuse,
+            specular,
+            fuzz: fuzz.min(1.0),
+        }
+    }
+}
+
+impl Material for Glossy {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord) -> bool {
+        let specular_chance = self.specular.x.max(self.specular.y).max(self.specular.z);
+        if random_double() < specular_chance {
+            let reflected = Vector::reflect(&r_in.direction.unit(), &rec.normal).unit()
+                + Vector::random_unit_vector() * self.fuzz;
+            // Fuzz can push the reflection below the surface; it is absorbed.
+            if reflected.dot(&rec.normal) <= 0.0 {
+                return false;
+            }
+            srec.attenuation = self.specular / specular_chance;
+            srec.pdf_ptr = None;
+            srec.skip_pdf = true;
+            srec.skip_pdf_ray = Ray::new(rec.p, reflected, r_in.time);
+        } else {
+            srec.attenuation = rec.albedo(self.diffuse.value(rec.u, rec.v, rec.p));
+            srec.pdf_ptr = Some(Arc::new(CosinePdf::new(rec.normal)));
+            srec.skip_pdf = false;
+        }
+        true
+    }
+    fn scattering_pdf(&self, _r_in: &Ray, rec: HitRecord, scattered: &mut Ray) -> f64 {
+        let cos_theta = rec.normal.dot(&scattered.direction.unit());
+        if cos_theta < 0.0 {
+            0.0
+        } else {
+            cos_theta / PI
+        }
+    }
+}
+
+// glTF metallic-roughness: metals reflect their base color, dielectrics pick a
+// white Schlick reflection (F0 = 0.04) and otherwise scatter diffusely. The
+// optional texture scales roughness by its green and metallic by its blue channel.
+pub struct MetallicRoughness {
+    base_color: Arc<dyn Texture>,
+    metallic: f64,
+    roughness: f64,
+    metallic_roughness: Option<Arc<dyn Texture>>,
+}
+
+impl MetallicRoughness {
+    pub fn new(
+        base_color: Arc<dyn Texture>,
+        metallic: f64,
+        roughness: f64,
+        metallic_roughness: Option<Arc<dyn Texture>>,
+    ) -> Self {
+        Self {
+            base_color,
+            metallic: metallic.clamp(0.0, 1.0),
+            roughness: roughness.clamp(0.0, 1.0),
+            metallic_roughness,
+        }
+    }
+}
+
+impl Material for MetallicRoughness {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord) -> bool {
+        let (mut metallic, mut roughness) = (self.metallic, self.roughness);
+        if let Some(tex) = &self.metallic_roughness {
+            let texel = tex.value(rec.u, rec.v, rec.p);
+            roughness *= texel.y;
+            metallic *= texel.z;
+        }
+        let base_color = rec.albedo(self.base_color.value(rec.u, rec.v, rec.p));
+        let unit_direction = r_in.direction.unit();
+        let cos_theta = rec.normal.dot(&(unit_direction * -1.0)).clamp(0.0, 1.0);
+        let is_metal = random_double() < metallic;
+        if is_metal || random_double() < Dielectric::reflectance(cos_theta, 1.5) {
+            let reflected = Vector::reflect(&unit_direction, &rec.normal)
+                + Vector::random_unit_vector() * (roughness * roughness);
+            // As with `Glossy`, a reflection pushed below the surface is absorbed.
+            if reflected.dot(&rec.normal) <= 0.0 {
+                return false;
+            }
+            srec.attenuation = if is_metal {
+                base_color
+            } else {
+                Vector::new(1.0, 1.0, 1.0)
+            };
+            srec.pdf_ptr = None;
+            srec.skip_pdf = true;
+            srec.skip_pdf_ray = Ray::new(rec.p, reflected, r_in.time);
+        } else {
+            srec.attenuation = base_color;
+            srec.pdf_ptr = Some(Arc::new(CosinePdf::new(rec.normal)));
+            srec.skip_pdf = false;
+        }
+        true
+    }
+    fn scattering_pdf(&self, _r_in: &Ray, rec: HitRecord, scattered: &mut Ray) -> f64 {
+        let cos_theta = rec.normal.dot(&scattered.direction.unit());
+        if cos_theta < 0.0 {
+            0.0
+        } else {
+            cos_theta / PI
+        }
+    }
+}
+
 pub struct Dielectric {
     refraction_index: f64,
 }
@@ -226,3 +345,115 @@ impl Material for NormalMappingMaterial {
         true
     }
 }
+
+// glTF MASK: the surface is cut away where the red channel of `alpha` is below
+// `cutoff`, and rays there continue straight through.
+pub struct AlphaMask {
+    base: Arc<dyn Material>,
+    alpha: Arc<dyn Texture>,
+    cutoff: f64,
+}
+
+impl AlphaMask {
+    pub fn new(base: Arc<dyn Material>, alpha: Arc<dyn Texture>, cutoff: f64) -> Self {
+        Self {
+            base,
+            alpha,
+            cutoff,
+        }
+    }
+    fn is_cut(&self, rec: &HitRecord) -> bool {
+        self.alpha.value(rec.u, rec.v, rec.p).x < self.cutoff
+    }
+}
+
+impl Material for AlphaMask {
+    fn emitted(&self, r_in: &Ray, rec: HitRecord, u: f64, v: f64, p: Vector) -> Vector {
+        if self.is_cut(&rec) {
+            Vector::default()
+        } else {
+            self.base.emitted(r_in, rec, u, v, p)
+        }
+    }
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord) -> bool {
+        if !self.is_cut(rec) {
+            return self.base.scatter(r_in, rec, srec);
+        }
+        srec.attenuation = Vector::new(1.0, 1.0, 1.0);
+        srec.pdf_ptr = None;
+        srec.skip_pdf = true;
+        srec.skip_pdf_ray = Ray::new(rec.p, r_in.direction, r_in.time);
+        true
+    }
+    fn scattering_pdf(&self, r_in: &Ray, rec: HitRecord, scattered: &mut Ray) -> f64 {
+        self.base.scattering_pdf(r_in, rec, scattered)
+    }
+}
+
+// MTL `d` / `Tr`: the surface is only there for a fraction `opacity` of the
+// rays, the rest continue straight through as if nothing was hit.
+pub struct Dissolve {
+    base: Arc<dyn Material>,
+    opacity: f64,
+}
+
+impl Dissolve {
+    pub fn new(base: Arc<dyn Material>, opacity: f64) -> Self {
+        Self {
+            base,
+            opacity: opacity.clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl Material for Dissolve {
+    fn emitted(&self, r_in: &Ray, rec: HitRecord, u: f64, v: f64, p: Vector) -> Vector {
+        self.base.emitted(r_in, rec, u, v, p) * self.opacity
+    }
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord) -> bool {
+        if random_double() < self.opacity {
+            return self.base.scatter(r_in, rec, srec);
+        }
+        srec.attenuation = Vector::new(1.0, 1.0, 1.0);
+        srec.pdf_ptr = None;
+        srec.skip_pdf = true;
+        srec.skip_pdf_ray = Ray::new(rec.p, r_in.direction, r_in.time);
+        true
+    }
+    fn scattering_pdf(&self, r_in: &Ray, rec: HitRecord, scattered: &mut Ray) -> f64 {
+        self.base.scattering_pdf(r_in, rec, scattered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rough_metal_never_reflects_below_the_surface() {
+        let metal = MetallicRoughness::new(
+            Arc::new(SolidColor::new(Vector::new(0.8, 0.8, 0.8))),
+            1.0,
+            1.0,
+            None,
+        );
+        let mut rec = HitRecord::new(Vector::default(), Vector::default(), 1.0, false);
+        let r_in = Ray::new(
+            Vector::new(-1.0, 0.0, 0.05),
+            Vector::new(1.0, 0.0, -0.05),
+            0.0,
+        );
+        rec.set_face_normal(&r_in, &Vector::new(0.0, 0.0, 1.0));
+        let mut absorbed = 0;
+        for _ in 0..1000 {
+            let mut srec: ScatterRecord = Default::default();
+            if metal.scatter(&r_in, &rec, &mut srec) {
+                assert!(srec.skip_pdf_ray.direction.dot(&rec.normal) > 0.0);
+            } else {
+                absorbed += 1;
+            }
+        }
+        // At grazing incidence a good share of the fuzzed reflections dip below.
+        assert!(absorbed > 0);
+    }
+}
@@ -0,0 +1,177 @@
+use crate::aabb::AABB;
+use crate::rtweekend::{degrees_to_radians, INFINITY};
+use crate::vec3::Vector;
+use std::ops::Mul;
+
+#[derive(Copy, Clone)]
+pub struct Matrix4 {
+    pub m: [[f64; 4]; 4],
+}
+
+impl Default for Matrix4 {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Matrix4 {
+    pub fn new(m: [[f64; 4]; 4]) -> Self {
+        Self { m }
+    }
+    pub fn identity() -> Self {
+        Self::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+    pub fn translation(offset: Vector) -> Self {
+        Self::new([
+            [1.0, 0.0, 0.0, offset.x],
+            [0.0, 1.0, 0.0, offset.y],
+            [0.0, 0.0, 1.0, offset.z],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+    pub fn scaling(factors: Vector) -> Self {
+        Self::new([
+            [factors.x, 0.0, 0.0, 0.0],
+            [0.0, factors.y, 0.0, 0.0],
+            [0.0, 0.0, factors.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+    pub fn rotation(axis: Vector, angle: f64) -> Self {
+        let a = axis.unit();
+        let (sin_theta, cos_theta) = degrees_to_radians(angle).sin_cos();
+        let t = 1.0 - cos_theta;
+        Self::new([
+            [
+                t * a.x * a.x + cos_theta,
+                t * a.x * a.y - sin_theta * a.z,
+                t * a.x * a.z + sin_theta * a.y,
+                0.0,
+            ],
+            [
+                t * a.x * a.y + sin_theta * a.z,
+                t * a.y * a.y + cos_theta,
+                t * a.y * a.z - sin_theta * a.x,
+                0.0,
+            ],
+            [
+                t * a.x * a.z - sin_theta * a.y,
+                t * a.y * a.z + sin_theta * a.x,
+                t * a.z * a.z + cos_theta,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+    pub fn rotation_x(angle: f64) -> Self {
+        Self::rotation(Vector::new(1.0, 0.0, 0.0), angle)
+    }
+    pub fn rotation_y(angle: f64) -> Self {
+        Self::rotation(Vector::new(0.0, 1.0, 0.0), angle)
+    }
+    pub fn rotation_z(angle: f64) -> Self {
+        Self::rotation(Vector::new(0.0, 0.0, 1.0), angle)
+    }
+    pub fn transpose(&self) -> Self {
+        let mut val = Self::identity();
+        for (i, row) in val.m.iter_mut().enumerate() {
+            for (j, x) in row.iter_mut().enumerate() {
+                *x = self.m[j][i];
+            }
+        }
+        val
+    }
+    pub fn determinant3(&self) -> f64 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.m;
+        let mut inv = Self::identity().m;
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&i, &j| a[i][col].abs().partial_cmp(&a[j][col].abs()).unwrap())
+                .unwrap();
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+            let scale = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= scale;
+                inv[col][j] *= scale;
+            }
+            for row in 0..4 {
+                if row != col {
+                    let factor = a[row][col];
+                    for j in 0..4 {
+                        a[row][j] -= factor * a[col][j];
+                        inv[row][j] -= factor * inv[col][j];
+                    }
+                }
+            }
+        }
+        Some(Self::new(inv))
+    }
+    pub fn column(&self, n: usize) -> Vector {
+        Vector::new(self.m[0][n], self.m[1][n], self.m[2][n])
+    }
+    pub fn transform_point(&self, p: Vector) -> Vector {
+        let m = &self.m;
+        Vector::new(
+            m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3],
+            m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3],
+            m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3],
+        )
+    }
+    pub fn transform_vector(&self, v: Vector) -> Vector {
+        let m = &self.m;
+        Vector::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+    pub fn transform_box(&self, bbox: &AABB) -> AABB {
+        let mut min = Vector::new(INFINITY, INFINITY, INFINITY);
+        let mut max = Vector::new(-INFINITY, -INFINITY, -INFINITY);
+        for i in 0..2 {
+            for j in 0..2 {
+                for k in 0..2 {
+                    let x = if i == 1 { bbox.x.max } else { bbox.x.min };
+                    let y = if j == 1 { bbox.y.max } else { bbox.y.min };
+                    let z = if k == 1 { bbox.z.max } else { bbox.z.min };
+                    let tester = self.transform_point(Vector::new(x, y, z));
+                    min.x = min.x.min(tester.x);
+                    min.y = min.y.min(tester.y);
+                    min.z = min.z.min(tester.z);
+                    max.x = max.x.max(tester.x);
+                    max.y = max.y.max(tester.y);
+                    max.z = max.z.max(tester.z);
+                }
+            }
+        }
+        AABB::point_new(&min, &max)
+    }
+}
+
+impl Mul for Matrix4 {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self::Output {
+        let mut val = Self::new([[0.0; 4]; 4]);
+        for i in 0..4 {
+            for j in 0..4 {
+                val.m[i][j] = (0..4).map(|k| self.m[i][k] * other.m[k][j]).sum();
+            }
+        }
+        val
+    }
+}
@@ -0,0 +1,280 @@
+use std::sync::Arc;
+
+use crate::aabb::AABB;
+use crate::bvh::BvhNode;
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::normal_mapping::NormalModifier;
+use crate::ray::Ray;
+use crate::rtweekend::{random_double, INFINITY};
+use crate::triangle::{intersect_watertight, uv_derivatives};
+use crate::vec3::Vector;
+
+// Vertex data is stored once per mesh and shared by every face, `normals`,
+// `uvs` and `colors` are either empty or indexed like `positions`. `normal_map`
+// changes the shading normals of every face, with tangents following the UVs.
+pub struct TriangleMesh {
+    pub positions: Vec<Vector>,
+    pub normals: Vec<Vector>,
+    pub uvs: Vec<(f64, f64)>,
+    pub colors: Vec<Vector>,
+    pub indices: Vec<[u32; 3]>,
+    pub mat: Arc<dyn Material>,
+    pub normal_map: Option<Arc<dyn NormalModifier>>,
+}
+
+impl TriangleMesh {
+    pub fn new(
+        positions: Vec<Vector>,
+        normals: Vec<Vector>,
+        uvs: Vec<(f64, f64)>,
+        indices: Vec<[u32; 3]>,
+        mat: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            positions,
+            normals,
+            uvs,
+            colors: Vec::new(),
+            indices,
+            mat,
+            normal_map: None,
+        }
+    }
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len()
+    }
+    pub fn vertices(&self, index: u32) -> [Vector; 3] {
+        let face = self.indices[index as usize];
+        [
+            self.positions[face[0] as usize],
+            self.positions[face[1] as usize],
+            self.positions[face[2] as usize],
+        ]
+    }
+    pub fn triangles(mesh: &Arc<Self>) -> Vec<Arc<dyn Hittable>> {
+        (0..mesh.triangle_count() as u32)
+            .map(|index| Arc::new(MeshTriangle::new(mesh.clone(), index)) as Arc<dyn Hittable>)
+            .collect()
+    }
+    pub fn bvh(self) -> BvhNode {
+        let mut objects = Self::triangles(&Arc::new(self));
+        let len = objects.len();
+        BvhNode::new(&mut objects, 0, len)
+    }
+}
+
+// A face of a `TriangleMesh`: just the shared mesh and a face index, so a
+// BVH leaf costs a pointer and an integer instead of a full `Triangle`.
+pub struct MeshTriangle {
+    mesh: Arc<TriangleMesh>,
+    index: u32,
+}
+
+impl MeshTriangle {
+    pub fn new(mesh: Arc<TriangleMesh>, index: u32) -> Self {
+        Self { mesh, index }
+    }
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(&self, r: &Ray, ray_t: &Interval) -> Option<HitRecord> {
+        let points = self.mesh.vertices(self.index);
+        let (t, weights) = intersect_watertight(r, &points, ray_t)?;
+        let face = self.mesh.indices[self.index as usize];
+        let mut rec = HitRecord {
+            t,
+            p: r.at(t),
+            mat: Some(self.mesh.mat.clone()),
+            ..Default::default()
+        };
+        let [p0, p1, p2] = points;
+        let geometric_normal = (p1 - p0).cross(&(p2 - p0)).unit();
+        rec.set_face_normal(r, &geometric_normal);
+        let mut shading_normal = geometric_normal;
+        if !self.mesh.normals.is_empty() {
+            shading_normal = Vector::default();
+            for (k, w) in weights.iter().enumerate() {
+                shading_normal = shading_normal + self.mesh.normals[face[k] as usize] * *w;
+            }
+            shading_normal = shading_normal.unit();
+        }
+        (rec.dpdu, rec.dpdv) = (p1 - p0, p2 - p0);
+        if self.mesh.uvs.is_empty() {
+            rec.u = weights[1];
+            rec.v = weights[2];
+        } else {
+            let uvs = face.map(|i| self.mesh.uvs[i as usize]);
+            for (uv, w) in uvs.iter().zip(weights.iter()) {
+                rec.u += uv.0 * w;
+                rec.v += uv.1 * w;
+            }
+            if let Some(derivatives) = uv_derivatives(&points, &uvs) {
+                (rec.dpdu, rec.dpdv) = derivatives;
+            }
+        }
+        if !self.mesh.colors.is_empty() {
+            let mut color = Vector::default();
+            for (k, w) in weights.iter().enumerate() {
+                color = color + self.mesh.colors[face[k] as usize] * *w;
+            }
+            rec.color = Some(color);
+        }
+        rec.normal = if rec.front_face {
+            shading_normal
+        } else {
+            shading_normal * -1.0
+        };
+        if let Some(normal_map) = &self.mesh.normal_map {
+            rec.normal = normal_map.normal(&rec);
+        }
+        Some(rec)
+    }
+    fn bounding_box(&self) -> AABB {
+        let [p0, p1, p2] = self.mesh.vertices(self.index);
+        AABB::box_new(&AABB::point_new(&p0, &p1), &AABB::point_new(&p0, &p2))
+    }
+    fn pdf_value(&self, origin: Vector, direction: Vector) -> f64 {
+        if let Some(rec) = self.hit(
+            &Ray::new(origin, direction, 0.0),
+            &Interval::new(0.001, INFINITY),
+        ) {
+            let [p0, p1, p2] = self.mesh.vertices(self.index);
+            let n = (p1 - p0).cross(&(p2 - p0));
+            let area = n.length() / 2.0;
+            let distance_squared = rec.t * rec.t * direction.length_square();
+            let cosine = (direction.dot(&n) / (direction.length() * n.length())).abs();
+            distance_squared / (cosine * area)
+        } else {
+            0.0
+        }
+    }
+    fn random(&self, origin: Vector) -> Vector {
+        let [p0, p1, p2] = self.mesh.vertices(self.index);
+        let mut a = random_double();
+        let mut b = random_double();
+        if a + b > 1.0 {
+            a = 1.0 - a;
+            b = 1.0 - b;
+        }
+        p0 + (p1 - p0) * a + (p2 - p0) * b - origin
+    }
+}
+
+// Area-weighted vertex normals of the faces.
+pub fn smooth_normals(positions: &[Vector], indices: &[[u32; 3]]) -> Vec<Vector> {
+    let mut normals = vec![Vector::default(); positions.len()];
+    for face in indices.iter() {
+        let [a, b, c] = face.map(|i| positions[i as usize]);
+        let n = (b - a).cross(&(c - a));
+        for &i in face.iter() {
+            normals[i as usize] = normals[i as usize] + n;
+        }
+    }
+    normals
+        .into_iter()
+        .map(|n| if n.near_zero() { n } else { n.unit() })
+        .collect()
+}
+
+pub fn mesh_list(meshes: Vec<TriangleMesh>) -> HittableList {
+    let mut objects: Vec<Arc<dyn Hittable>> = Vec::new();
+    for mesh in meshes {
+        objects.extend(TriangleMesh::triangles(&Arc::new(mesh)));
+    }
+    let mut val = HittableList::new();
+    if !objects.is_empty() {
+        let len = objects.len();
+        val.add(Arc::new(BvhNode::new(&mut objects, 0, len)));
+    }
+    val
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::triangle::Triangle;
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn empty_mesh_builds_a_bvh_that_is_never_hit() {
+        let mesh = TriangleMesh::new(Vec::new(), Vec::new(), Vec::new(), Vec::new(), material());
+        let bvh = mesh.bvh();
+        let r = Ray::new(Vector::new(0.0, 0.0, 2.0), Vector::new(0.0, 0.0, -1.0), 0.0);
+        assert!(bvh.hit(&r, &Interval::new(0.0, INFINITY)).is_none());
+    }
+
+    #[test]
+    fn flat_mesh_is_hit_through_its_bvh() {
+        let mesh = TriangleMesh::new(
+            vec![
+                Vector::new(0.0, 0.0, 0.0),
+                Vector::new(1.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+            ],
+            Vec::new(),
+            Vec::new(),
+            vec![[0, 1, 2]],
+            material(),
+        );
+        let r = Ray::new(
+            Vector::new(0.25, 0.25, 2.0),
+            Vector::new(0.0, 0.0, -1.0),
+            0.0,
+        );
+        let rec = mesh.bvh().hit(&r, &Interval::new(0.0, INFINITY));
+        assert!((rec.expect("the ray crosses the face").t - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn mesh_and_triangle_list_hit_alike() {
+        // A bumpy grid, so that faces overlap in every axis and BVH splits vary.
+        let n = 12;
+        let mut positions = Vec::new();
+        for j in 0..=n {
+            for i in 0..=n {
+                let z = ((i * 7 + j * 13) % 5) as f64 * 0.2;
+                positions.push(Vector::new(i as f64, j as f64, z));
+            }
+        }
+        let mut indices = Vec::new();
+        for j in 0..n {
+            for i in 0..n {
+                let a = j * (n + 1) + i;
+                indices.push([a, a + 1, a + n + 1]);
+                indices.push([a + 1, a + n + 2, a + n + 1]);
+            }
+        }
+        let mut triangles: Vec<Arc<dyn Hittable>> = indices
+            .iter()
+            .map(|face| {
+                let [a, b, c] = face.map(|i| positions[i as usize]);
+                Arc::new(Triangle::new(a, b, c, material())) as Arc<dyn Hittable>
+            })
+            .collect();
+        let len = triangles.len();
+        let list = BvhNode::new(&mut triangles, 0, len);
+        let mesh = TriangleMesh::new(positions, Vec::new(), Vec::new(), indices, material()).bvh();
+
+        let ray_t = Interval::new(0.001, INFINITY);
+        for k in 0..400 {
+            let (x, y) = ((k % 20) as f64 * 0.61 - 0.3, (k / 20) as f64 * 0.61 - 0.3);
+            let r = Ray::new(Vector::new(x, y, 5.0), Vector::new(0.3, -0.2, -1.0), 0.0);
+            match (list.hit(&r, &ray_t), mesh.hit(&r, &ray_t)) {
+                (None, None) => {}
+                (Some(a), Some(b)) => {
+                    assert!((a.t - b.t).abs() < 1e-12, "ray {}", k);
+                    assert!((a.normal - b.normal).near_zero(), "ray {}", k);
+                    assert_eq!(a.front_face, b.front_face);
+                }
+                _ => panic!("ray {} hit only one of the two", k),
+            }
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use std::sync::Arc;
+
+use crate::aabb::AABB;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::matrix::Matrix4;
+use crate::ray::Ray;
+use crate::rtweekend::{degrees_to_radians, PI};
+use crate::vec3::Vector;
+
+const BOUNDS_STEPS: u32 = 16;
+
+// Rotation is stored as XYZ Euler angles in degrees and interpolated per angle,
+// so a pair of keyframes can describe spins of more than half a turn.
+#[derive(Copy, Clone)]
+pub struct TransformKeyframe {
+    pub time: f64,
+    pub translation: Vector,
+    pub rotation: Vector,
+    pub scale: Vector,
+}
+
+impl TransformKeyframe {
+    pub fn new(time: f64, translation: Vector, rotation: Vector, scale: Vector) -> Self {
+        Self {
+            time,
+            translation,
+            rotation,
+            scale,
+        }
+    }
+    pub fn matrix(&self) -> Matrix4 {
+        Matrix4::translation(self.translation)
+            * Matrix4::rotation_z(self.rotation.z)
+            * Matrix4::rotation_y(self.rotation.y)
+            * Matrix4::rotation_x(self.rotation.x)
+            * Matrix4::scaling(self.scale)
+    }
+    // The inverse of `matrix`, built from the undone steps in reverse order
+    // rather than by eliminating a 4x4 matrix. None when a scale is zero.
+    pub fn inverse_matrix(&self) -> Option<Matrix4> {
+        if self.scale.x == 0.0 || self.scale.y == 0.0 || self.scale.z == 0.0 {
+            return None;
+        }
+        let unscale = Vector::new(1.0 / self.scale.x, 1.0 / self.scale.y, 1.0 / self.scale.z);
+        Some(
+            Matrix4::scaling(unscale)
+                * Matrix4::rotation_x(-self.rotation.x)
+                * Matrix4::rotation_y(-self.rotation.y)
+                * Matrix4::rotation_z(-self.rotation.z)
+                * Matrix4::translation(self.translation * -1.0),
+        )
+    }
+    fn lerp(&self, other: &Self, time: f64) -> Self {
+        let s = (time - self.time) / (other.time - self.time);
+        Self {
+            time,
+            translation: self.translation + (other.translation - self.translation) * s,
+            rotation: self.rotation + (other.rotation - self.rotation) * s,
+            scale: self.scale + (other.scale - self.scale) * s,
+        }
+    }
+}
+
+pub struct AnimatedTransform {
+    object: Arc<dyn Hittable>,
+    keyframes: Vec<TransformKeyframe>,
+    bbox: AABB,
+}
+
+impl AnimatedTransform {
+    // None without keyframes or when a keyframe time is NaN.
+    pub fn new(obj: Arc<dyn Hittable>, mut keyframes: Vec<TransformKeyframe>) -> Option<Self> {
+        if keyframes.is_empty() || keyframes.iter().any(|key| key.time.is_nan()) {
+            return None;
+        }
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        let mut val = Self {
+            object: obj,
+            keyframes,
+            bbox: AABB::default(),
+        };
+        val.bbox = val.motion_bounds();
+        Some(val)
+    }
+    pub fn keyframe_at(&self, time: f64) -> TransformKeyframe {
+        let first = &self.keyframes[0];
+        let last = &self.keyframes[self.keyframes.len() - 1];
+        if time <= first.time {
+            return TransformKeyframe { time, ..*first };
+        }
+        if time >= last.time {
+            return TransformKeyframe { time, ..*last };
+        }
+        let k = self
+            .keyframes
+            .iter()
+            .rposition(|key| key.time <= time)
+            .unwrap();
+        self.keyframes[k].lerp(&self.keyframes[k + 1], time)
+    }
+    // Translation and scale move each box corner linearly between samples, so only
+    // the rotation needs padding: a point at distance r swept through an angle
+    // `step` leaves the chord between samples by at most r * (1 - cos(step / 2)).
+    fn motion_bounds(&self) -> AABB {
+        let object_box = self.object.bounding_box();
+        let mut radius: f64 = 0.0;
+        for x in [object_box.x.min, object_box.x.max] {
+            for y in [object_box.y.min, object_box.y.max] {
+                for z in [object_box.z.min, object_box.z.max] {
+                    radius = radius.max(Vector::new(x, y, z).length());
+                }
+            }
+        }
+        let mut bbox = self.keyframes[0].matrix().transform_box(&object_box);
+        for pair in self.keyframes.windows(2) {
+            let (start, end) = (&pair[0], &pair[1]);
+            let turn = end.rotation - start.rotation;
+            let step = degrees_to_radians(turn.x.abs() + turn.y.abs() + turn.z.abs())
+                / (BOUNDS_STEPS as f64);
+            let max_scale = [start.scale, end.scale]
+                .iter()
+                .map(|s| s.x.abs().max(s.y.abs()).max(s.z.abs()))
+                .fold(0.0, f64::max);
+            let pad = 2.0 * radius * max_scale * (1.0 - (step.min(PI) / 2.0).cos());
+            for i in 0..=BOUNDS_STEPS {
+                let time =
+                    start.time + (end.time - start.time) * (i as f64) / (BOUNDS_STEPS as f64);
+                let key = start.lerp(end, time);
+                let sample = key.matrix().transform_box(&object_box);
+                let sample = AABB::new(
+                    sample.x.expand(pad),
+                    sample.y.expand(pad),
+                    sample.z.expand(pad),
+                );
+                bbox = AABB::box_new(&bbox, &sample);
+            }
+        }
+        bbox
+    }
+}
+
+impl Hittable for AnimatedTransform {
+    fn hit(&self, r: &Ray, ray_t: &Interval) -> Option<HitRecord> {
+        let key = self.keyframe_at(r.time);
+        let inverse = key.inverse_matrix()?;
+        let matrix = key.matrix();
+        let object_r = Ray::new(
+            inverse.transform_point(r.origin),
+            inverse.transform_vector(r.direction),
+            r.time,
+        );
+        let mut rec = self.object.hit(&object_r, ray_t)?;
+        rec.p = matrix.transform_point(rec.p);
+        rec.normal = inverse.transpose().transform_vector(rec.normal).unit();
+        rec.dpdu = matrix.transform_vector(rec.dpdu);
+        rec.dpdv = matrix.transform_vector(rec.dpdv);
+        Some(rec)
+    }
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+    // Light sampling has no ray time, so like a moving sphere the object is
+    // sampled where it is at time 0, with the same Jacobian as `Transform`.
+    fn pdf_value(&self, origin: Vector, direction: Vector) -> f64 {
+        let inverse = match self.keyframe_at(0.0).inverse_matrix() {
+            Some(inverse) => inverse,
+            None => return 0.0,
+        };
+        let object_direction = inverse.transform_vector(direction.unit());
+        let length = object_direction.length();
+        let object_pdf = self
+            .object
+            .pdf_value(inverse.transform_point(origin), object_direction);
+        object_pdf * inverse.determinant3().abs() / (length * length * length)
+    }
+    fn random(&self, origin: Vector) -> Vector {
+        let key = self.keyframe_at(0.0);
+        let inverse = match key.inverse_matrix() {
+            Some(inverse) => inverse,
+            None => return Vector::new(1.0, 0.0, 0.0),
+        };
+        key.matrix()
+            .transform_vector(self.object.random(inverse.transform_point(origin)))
+    }
+}
@@ -1,108 +1,393 @@
 use std::sync::Arc;
 
 use crate::aabb::AABB;
+use crate::displacement::texture_height;
 use crate::hittable::HitRecord;
 use crate::hittable::Hittable;
-use crate::image::RtwImage;
 use crate::interval::Interval;
 use crate::material::Material;
+use crate::onb::Onb;
+use crate::quad::Quad;
 use crate::ray::Ray;
+use crate::texture::{ImageTexture, Texture};
 use crate::vec3::Vector;
 
+// Surface detail that only changes how a hit is shaded, such as a normal map.
+pub trait NormalModifier: Send + Sync {
+    // The new shading normal, on the same side of the surface as `rec.normal`.
+    fn normal(&self, rec: &HitRecord) -> Vector;
+}
+
+// The normal on the outside of the surface, whichever side the ray hit.
+fn outward_normal(rec: &HitRecord) -> Vector {
+    if rec.front_face {
+        rec.normal
+    } else {
+        rec.normal * -1.0
+    }
+}
+
+// The tangent and bitangent around the outward normal: `dpdu` made orthogonal
+// to it, and the direction across that agrees with `dpdv`, so mirrored UVs flip
+// the frame with them. Primitives without derivatives get an arbitrary frame.
+pub fn tangent_frame(rec: &HitRecord) -> (Vector, Vector) {
+    let normal = outward_normal(rec);
+    let tangent = rec.dpdu - normal * normal.dot(&rec.dpdu);
+    if tangent.near_zero() {
+        let mut frame: Onb = Default::default();
+        frame.build_from_w(normal);
+        return (frame.axis[0], frame.axis[1]);
+    }
+    let tangent = tangent.unit();
+    let bitangent = normal.cross(&tangent);
+    if bitangent.dot(&rec.dpdv) < 0.0 {
+        (tangent, bitangent * -1.0)
+    } else {
+        (tangent, bitangent)
+    }
+}
+
+// Which way the green channel of a normal map points: along +v in OpenGL maps
+// (files named `_NormalGL`), against it in DirectX ones (`_NormalDX`).
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum NormalMapConvention {
+    OpenGl,
+    DirectX,
+}
+
+// A tangent-space normal map. `strength` scales the tilt of the stored normals:
+// zero leaves the surface flat and values above one exaggerate the relief.
+pub struct NormalMap {
+    pub texture: Arc<dyn Texture>,
+    pub convention: NormalMapConvention,
+    pub strength: f64,
+}
+
+impl NormalMap {
+    pub fn new(texture: Arc<dyn Texture>, convention: NormalMapConvention, strength: f64) -> Self {
+        Self {
+            texture,
+            convention,
+            strength,
+        }
+    }
+    pub fn image_new(image_filename: &str, convention: NormalMapConvention) -> Self {
+        Self::new(Arc::new(ImageTexture::new(image_filename)), convention, 1.0)
+    }
+}
+
+impl NormalModifier for NormalMap {
+    fn normal(&self, rec: &HitRecord) -> Vector {
+        let texel = self.texture.value(rec.u, rec.v, rec.p);
+        let green = match self.convention {
+            NormalMapConvention::OpenGl => texel.y * 2.0 - 1.0,
+            NormalMapConvention::DirectX => 1.0 - texel.y * 2.0,
+        };
+        // The map describes the outside; a hit from behind sees the same
+        // normal turned around.
+        let (tangent, bitangent) = tangent_frame(rec);
+        let mapped = tangent * ((texel.x * 2.0 - 1.0) * self.strength)
+            + bitangent * (green * self.strength)
+            + outward_normal(rec) * (texel.z * 2.0 - 1.0);
+        if mapped.near_zero() {
+            rec.normal
+        } else if rec.front_face {
+            mapped.unit()
+        } else {
+            mapped.unit() * -1.0
+        }
+    }
+}
+
+// Where a `BumpMap` samples its texture: at the hit's UVs, or at the hit point
+// itself for solid textures such as `NoiseTexture`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum BumpSpace {
+    Uv,
+    World,
+}
+
+// Shades the surface as if it were pushed out along its normal by `scale` times
+// the gray level of `texture`, without moving it. The slopes come from finite
+// differences `delta` apart, in UV units or world units depending on `space`.
+// No single default suits every texture: `delta` should be about the size of
+// the finest detail, such as one texel (1 / width) for an image in UV space.
+pub struct BumpMap {
+    pub texture: Arc<dyn Texture>,
+    pub scale: f64,
+    pub space: BumpSpace,
+    pub delta: f64,
+}
+
+impl BumpMap {
+    pub fn new(texture: Arc<dyn Texture>, scale: f64, space: BumpSpace, delta: f64) -> Self {
+        Self {
+            texture,
+            scale,
+            space,
+            delta,
+        }
+    }
+    fn height(&self, u: f64, v: f64, p: Vector) -> f64 {
+        self.scale * texture_height(self.texture.as_ref(), u, v, p)
+    }
+}
+
+impl NormalModifier for BumpMap {
+    fn normal(&self, rec: &HitRecord) -> Vector {
+        // Heights rise along the outward normal whichever side is seen.
+        let outward = outward_normal(rec);
+        let h = self.height(rec.u, rec.v, rec.p);
+        let bumped = match self.space {
+            BumpSpace::Uv => {
+                let (dpdu, dpdv) = if rec.dpdu.near_zero() || rec.dpdv.near_zero() {
+                    tangent_frame(rec)
+                } else {
+                    (rec.dpdu, rec.dpdv)
+                };
+                let d = self.delta;
+                let dhdu = (self.height(rec.u + d, rec.v, rec.p + dpdu * d) - h) / d;
+                let dhdv = (self.height(rec.u, rec.v + d, rec.p + dpdv * d) - h) / d;
+                let normal = (dpdu + outward * dhdu).cross(&(dpdv + outward * dhdv));
+                if normal.dot(&outward) < 0.0 {
+                    normal * -1.0
+                } else {
+                    normal
+                }
+            }
+            BumpSpace::World => {
+                let d = self.delta;
+                let gradient = Vector::new(
+                    self.height(rec.u, rec.v, rec.p + Vector::new(d, 0.0, 0.0)) - h,
+                    self.height(rec.u, rec.v, rec.p + Vector::new(0.0, d, 0.0)) - h,
+                    self.height(rec.u, rec.v, rec.p + Vector::new(0.0, 0.0, d)) - h,
+                ) / d;
+                outward - (gradient - outward * outward.dot(&gradient))
+            }
+        };
+        if bumped.near_zero() {
+            rec.normal
+        } else if rec.front_face {
+            bumped.unit()
+        } else {
+            bumped.unit() * -1.0
+        }
+    }
+}
+
+// Any object with its shading normals changed by `modifier`; the geometry, and
+// so shadows and silhouettes, stay those of `object`.
 pub struct NormalMapping {
-    pub image: RtwImage,
-    q: Vector,
-    u: Vector,
-    v: Vector,
-    w: Vector,
-    normal: Vector,
-    mat: Arc<dyn Material>,
-    bbox: AABB,
-    d: f64,
+    pub object: Arc<dyn Hittable>,
+    pub modifier: Arc<dyn NormalModifier>,
 }
 
 impl NormalMapping {
-    pub fn new(
+    pub fn new(object: Arc<dyn Hittable>, modifier: Arc<dyn NormalModifier>) -> Self {
+        Self { object, modifier }
+    }
+    // A quad with an OpenGL-style normal map image stretched over it.
+    pub fn quad_new(
         image_filename: &str,
         a: Vector,
         b: Vector,
         c: Vector,
         d: Arc<dyn Material>,
     ) -> Self {
-        let mut val = Self {
-            image: RtwImage::new(image_filename),
-            q: a,
-            u: b,
-            v: c,
-            w: Default::default(),
-            normal: Default::default(),
-            mat: d,
-            bbox: Default::default(),
-            d: 0.0,
-        };
-        let n = b.cross(&c);
-        val.normal = n.unit();
-        val.d = val.normal.dot(&a);
-        val.w = n / n.length_square();
-        val.set_bounding_box();
-        val
-    }
-    pub fn set_bounding_box(&mut self) {
-        let bbox_diagonal1 = AABB::point_new(&self.q, &(self.q + self.u + self.v));
-        let bbox_diagonal2 = AABB::point_new(&(self.q + self.u), &(self.q + self.v));
-        self.bbox = AABB::box_new(&bbox_diagonal1, &bbox_diagonal2);
-    }
-    fn is_interior(a: f64, b: f64) -> Option<HitRecord> {
-        let mut val: HitRecord = Default::default();
-        let unit_interval = Interval::new(0.0, 1.0);
-        if !unit_interval.contains(a) || !unit_interval.contains(b) {
-            return None;
-        }
-        val.u = a;
-        val.v = b;
-        Some(val)
-    }
-    pub fn get_normal(&self, u: f64, v: f64) -> Vector {
-        let u = Interval::new(0.0, 1.0).clamp(u);
-        let v = 1.0 - Interval::new(0.0, 1.0).clamp(v);
-        let i = (self.image.image_width as f64 * u) as i32;
-        let j = (self.image.image_height as f64 * v) as i32;
-        let pixel = self.image.pixel_data(i, j);
-        Vector::new(
-            (pixel[0] as f64 / 255.99) * 2.0 - 1.0,
-            (pixel[1] as f64 / 255.99) * 2.0 - 1.0,
-            (pixel[2] as f64 / 255.99) * 2.0 - 1.0,
+        Self::new(
+            Arc::new(Quad::new(a, b, c, d)),
+            Arc::new(NormalMap::image_new(
+                image_filename,
+                NormalMapConvention::OpenGl,
+            )),
         )
     }
 }
 
 impl Hittable for NormalMapping {
     fn hit(&self, r: &Ray, ray_t: &Interval) -> Option<HitRecord> {
-        let denom = self.normal.dot(&r.direction);
-        if denom.abs() < 1e-8 {
-            return None;
-        }
-        let t = (self.d - self.normal.dot(&r.origin)) / denom;
-        if !ray_t.contains(t) {
-            return None;
+        let mut rec = self.object.hit(r, ray_t)?;
+        rec.normal = self.modifier.normal(&rec);
+        Some(rec)
+    }
+    fn bounding_box(&self) -> AABB {
+        self.object.bounding_box()
+    }
+    fn pdf_value(&self, origin: Vector, direction: Vector) -> f64 {
+        self.object.pdf_value(origin, direction)
+    }
+    fn random(&self, origin: Vector) -> Vector {
+        self.object.random(origin)
+    }
+    fn hit_all(&self, r: &Ray, ray_t: &Interval) -> Vec<HitRecord> {
+        let mut crossings = self.object.hit_all(r, ray_t);
+        for rec in crossings.iter_mut() {
+            rec.normal = self.modifier.normal(rec);
         }
-        let intersection = r.at(t);
-        let planar_hitpt_vector = intersection - self.q;
-        let alpha = self.w.dot(&planar_hitpt_vector.cross(&self.v));
-        let beta = self.w.dot(&self.u.cross(&planar_hitpt_vector));
-        if let Some(mut val) = Self::is_interior(alpha, beta) {
-            val.t = t;
-            val.p = intersection;
-            val.mat = Some(self.mat.clone());
-            val.normal = self.get_normal(alpha, beta) + self.normal;
-            let normal = val.normal;
-            val.set_face_normal(r, &normal);
-            Some(val)
-        } else {
-            None
+        crossings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cylinder::Cylinder;
+    use crate::material::Lambertian;
+    use crate::texture::{NoiseTexture, SolidColor};
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5)))
+    }
+
+    // A unit quad in the xy plane facing +z, with u along x and v along y.
+    fn mapped_quad(texel: Vector, convention: NormalMapConvention, strength: f64) -> NormalMapping {
+        NormalMapping::new(
+            Arc::new(Quad::new(
+                Vector::new(0.0, 0.0, 0.0),
+                Vector::new(1.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+                material(),
+            )),
+            Arc::new(NormalMap::new(
+                Arc::new(SolidColor::new(texel)),
+                convention,
+                strength,
+            )),
+        )
+    }
+
+    fn shading_normal(object: &dyn Hittable, from_z: f64) -> Vector {
+        let r = Ray::new(
+            Vector::new(0.5, 0.5, from_z),
+            Vector::new(0.0, 0.0, -from_z),
+            0.0,
+        );
+        object
+            .hit(&r, &Interval::new(0.001, f64::INFINITY))
+            .expect("the ray crosses the quad")
+            .normal
+    }
+
+    fn assert_near(a: Vector, b: Vector) {
+        assert!((a - b).near_zero(), "({}, {}, {})", a.x, a.y, a.z);
+    }
+
+    #[test]
+    fn back_hits_see_the_front_normal_reversed() {
+        let quad = mapped_quad(Vector::new(0.8, 0.3, 0.9), NormalMapConvention::OpenGl, 1.0);
+        let front = shading_normal(&quad, 1.0);
+        let back = shading_normal(&quad, -1.0);
+        assert!(front.z > 0.0);
+        assert_near(back, front * -1.0);
+    }
+
+    #[test]
+    fn directx_maps_flip_green() {
+        let texel = Vector::new(0.5, 0.8, 0.9);
+        let gl = shading_normal(&mapped_quad(texel, NormalMapConvention::OpenGl, 1.0), 1.0);
+        let dx = shading_normal(&mapped_quad(texel, NormalMapConvention::DirectX, 1.0), 1.0);
+        assert!(gl.y > 0.0);
+        assert_near(dx, Vector::new(gl.x, -gl.y, gl.z));
+    }
+
+    #[test]
+    fn curved_surfaces_tilt_along_their_uvs() {
+        let cylinder: Arc<dyn Hittable> = Arc::new(Cylinder::new(
+            Vector::new(0.0, 0.0, -1.0),
+            Vector::new(0.0, 0.0, 2.0),
+            1.0,
+            false,
+            material(),
+        ));
+        let radial = Vector::new(0.6, 0.8, 0.0);
+        let r = Ray::new(radial * 5.0, radial * -1.0, 0.0);
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        let plain = cylinder
+            .hit(&r, &ray_t)
+            .expect("the ray crosses the cylinder");
+        // u runs around the cylinder and v along its axis.
+        let tangent = plain.dpdu.unit();
+        assert!(tangent.cross(&Vector::new(-0.8, 0.6, 0.0)).near_zero());
+        assert!((plain.dpdv.unit() - Vector::new(0.0, 0.0, 1.0)).near_zero());
+
+        let normal = |texel: Vector| {
+            let mapping = NormalMapping::new(
+                cylinder.clone(),
+                Arc::new(NormalMap::new(
+                    Arc::new(SolidColor::new(texel)),
+                    NormalMapConvention::OpenGl,
+                    1.0,
+                )),
+            );
+            mapping
+                .hit(&r, &ray_t)
+                .expect("the ray crosses the cylinder")
+                .normal
+        };
+        // Red tilts the normal towards +u, green towards +v.
+        let around = normal(Vector::new(0.75, 0.5, 0.75));
+        assert!(around.dot(&tangent) > 0.5 && around.z.abs() < 1e-9);
+        let along = normal(Vector::new(0.5, 0.75, 0.75));
+        assert!(along.z > 0.5 && along.dot(&tangent).abs() < 1e-9);
+    }
+
+    #[test]
+    fn strength_scales_the_tilt() {
+        let texel = Vector::new(0.75, 0.5, 0.75);
+        let tilt = |strength: f64| {
+            let n = shading_normal(
+                &mapped_quad(texel, NormalMapConvention::OpenGl, strength),
+                1.0,
+            );
+            n.x / n.z
+        };
+        assert!(tilt(0.0).abs() < 1e-12);
+        assert!((tilt(1.0) - 1.0).abs() < 1e-12);
+        assert!((tilt(2.0) - 2.0).abs() < 1e-12);
+    }
+
+    // Gray level equal to x, a slope of one in world space.
+    struct Ramp;
+
+    impl Texture for Ramp {
+        fn value(&self, _u: f64, _v: f64, p: Vector) -> Vector {
+            Vector::new(p.x, p.x, p.x)
         }
     }
-    fn bounding_box(&self) -> AABB {
-        self.bbox
+
+    fn bumped_quad(texture: Arc<dyn Texture>, scale: f64) -> NormalMapping {
+        NormalMapping::new(
+            Arc::new(Quad::new(
+                Vector::new(0.0, 0.0, 0.0),
+                Vector::new(1.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+                material(),
+            )),
+            Arc::new(BumpMap::new(texture, scale, BumpSpace::World, 1e-4)),
+        )
+    }
+
+    #[test]
+    fn world_bumps_follow_the_height_gradient() {
+        let quad = bumped_quad(Arc::new(Ramp), 0.5);
+        assert_near(
+            shading_normal(&quad, 1.0),
+            Vector::new(-0.5, 0.0, 1.0).unit(),
+        );
+        assert_near(
+            shading_normal(&quad, -1.0),
+            Vector::new(0.5, 0.0, -1.0).unit(),
+        );
+    }
+
+    #[test]
+    fn world_bumps_shade_solid_noise() {
+        let quad = bumped_quad(Arc::new(NoiseTexture::new(4.0)), 0.05);
+        let front = shading_normal(&quad, 1.0);
+        let back = shading_normal(&quad, -1.0);
+        assert!(front.z > 0.0 && !(front - Vector::new(0.0, 0.0, 1.0)).near_zero());
+        assert_near(back, front * -1.0);
+        let flat = shading_normal(&bumped_quad(Arc::new(NoiseTexture::new(4.0)), 0.0), 1.0);
+        assert_near(flat, Vector::new(0.0, 0.0, 1.0));
     }
 }
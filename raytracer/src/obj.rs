@@ -1,78 +1,261 @@
-use crate::bvh::BvhNode;
-use crate::material::Material;
-use crate::triangle::Triangle;
+use crate::aabb::AABB;
+use crate::hittable_list::HittableList;
+use crate::material::{Dielectric, DiffuseLight, Dissolve, Glossy, Lambertian, Material};
+use crate::matrix::Matrix4;
+use crate::mesh::{mesh_list, TriangleMesh};
+use crate::normal_mapping::{NormalMap, NormalMapConvention, NormalModifier};
+use crate::subdivision::{PolygonMesh, Subdivision};
+use crate::texture::{ImageTexture, SolidColor, Texture};
 use crate::vec3::Vector;
-use crate::{hittable_list::HittableList, material::Lambertian};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+#[derive(Debug)]
+pub enum ObjError {
+    NotFound(PathBuf),
+    Parse(PathBuf, tobj::LoadError),
+    SingularTransform,
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::NotFound(path) => write!(f, "OBJ file '{}' not found", path.display()),
+            ObjError::Parse(path, e) => write!(f, "failed to load '{}': {}", path.display(), e),
+            ObjError::SingularTransform => write!(f, "OBJ import transform is not invertible"),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum UpAxis {
+    Y,
+    Z,
+}
+
+impl UpAxis {
+    // Brings the file's up axis onto the renderer's +Y; a Z-up file's -Y
+    // (forward in most DCC tools) ends up along +Z.
+    pub fn matrix(&self) -> Matrix4 {
+        match self {
+            UpAxis::Y => Matrix4::identity(),
+            UpAxis::Z => Matrix4::rotation_x(-90.0),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum MaterialGrouping {
+    Merge,
+    Split,
+}
+
+// Faces sharing a material are always gathered into one `TriangleMesh`. With
+// `Merge` every face goes into a single BVH, with `Split` each material gets its
+// own BVH as a separate entry of the returned list, in MTL order with
+// material-less faces last. The up-axis conversion and `recenter` are applied
+// before `transform`. With `subdivision` every object is refined on its original
+// polygons and gets smooth normals in place of the file's.
+#[derive(Copy, Clone)]
+pub struct ObjImportOptions {
+    pub transform: Matrix4,
+    pub up_axis: UpAxis,
+    pub flip_winding: bool,
+    pub grouping: MaterialGrouping,
+    pub recenter: bool,
+    pub subdivision: Option<Subdivision>,
+}
+
+impl Default for ObjImportOptions {
+    fn default() -> Self {
+        Self {
+            transform: Matrix4::identity(),
+            up_axis: UpAxis::Y,
+            flip_winding: false,
+            grouping: MaterialGrouping::Merge,
+            recenter: false,
+            subdivision: None,
+        }
+    }
+}
+
+fn polygon_mesh(mesh: &tobj::Mesh, matrix: &Matrix4, flip_winding: bool) -> PolygonMesh {
+    let positions = mesh
+        .positions
+        .chunks(3)
+        .map(|p| matrix.transform_point(Vector::new(p[0] as f64, p[1] as f64, p[2] as f64)))
+        .collect();
+    let uvs = mesh
+        .texcoords
+        .chunks(2)
+        .map(|t| (t[0] as f64, t[1] as f64))
+        .collect();
+    let has_uvs = !mesh.texcoord_indices.is_empty();
+    let mut faces = Vec::new();
+    let mut uv_faces = Vec::new();
+    let mut start = 0;
+    while start < mesh.indices.len() {
+        // Without arities every face is a triangle.
+        let arity = mesh
+            .face_arities
+            .get(faces.len())
+            .map_or(3, |&n| n as usize);
+        let range = start..start + arity;
+        start += arity;
+        let mut face = mesh.indices[range.clone()].to_vec();
+        let mut uv_face = if has_uvs {
+            mesh.texcoord_indices[range].to_vec()
+        } else {
+            Vec::new()
+        };
+        if flip_winding {
+            face.reverse();
+            uv_face.reverse();
+        }
+        faces.push(face);
+        if has_uvs {
+            uv_faces.push(uv_face);
+        }
+    }
+    PolygonMesh::new(positions, faces, uvs, uv_faces)
+}
+
+struct ObjMaterial {
+    mat: Arc<dyn Material>,
+    normal_map: Option<Arc<dyn NormalModifier>>,
+}
+
 pub fn get_obj(obj_filename: &str, scale: f64) -> HittableList {
-    let mut val = HittableList::new();
-    let obj = tobj::load_obj(
-        format!("objects/{}", obj_filename),
+    let options = ObjImportOptions {
+        transform: Matrix4::scaling(Vector::new(scale, scale, scale)),
+        ..Default::default()
+    };
+    load_obj(Path::new("objects").join(obj_filename), &options)
+        .unwrap_or_else(|e| panic!("Failed to load OBJ file: {}", e))
+}
+
+pub fn load_obj<P: AsRef<Path>>(
+    path: P,
+    options: &ObjImportOptions,
+) -> Result<HittableList, ObjError> {
+    let path = path.as_ref();
+    if !path.is_file() {
+        return Err(ObjError::NotFound(path.to_path_buf()));
+    }
+    let dir = path.parent().unwrap_or(Path::new("."));
+    // Subdivision needs the polygons as they are, with vertices shared across
+    // UV seams, so those are not triangulated and keep separate UV indices.
+    let (models, materials) = tobj::load_obj(
+        path,
         &tobj::LoadOptions {
-            single_index: false,
-            triangulate: false,
+            single_index: options.subdivision.is_none(),
+            triangulate: options.subdivision.is_none(),
             ignore_points: true,
             ignore_lines: true,
         },
-    );
-    let (models, materials) = obj.expect("Failed to load OBJ file");
-    let materials = materials.expect("Failed to load MTL file");
-    for (_i, m) in models.iter().enumerate() {
+    )
+    .map_err(|e| ObjError::Parse(path.to_path_buf(), e))?;
+    // A missing or broken MTL only costs the materials, not the geometry.
+    let materials = materials.unwrap_or_else(|e| {
+        eprintln!(
+            "WARNING: could not load MTL for '{}': {}",
+            path.display(),
+            e
+        );
+        Vec::new()
+    });
+    let obj_materials: Vec<ObjMaterial> = materials.iter().map(|m| obj_material(m, dir)).collect();
+    let default_mat: Arc<dyn Material> = Arc::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5)));
+
+    let up = options.up_axis.matrix();
+    let mut bbox = AABB::default();
+    for m in models.iter() {
+        for p in m.mesh.positions.chunks(3) {
+            let p = up.transform_point(Vector::new(p[0] as f64, p[1] as f64, p[2] as f64));
+            bbox = AABB::box_new(&bbox, &AABB::point_new(&p, &p));
+        }
+    }
+    let offset = if options.recenter && !models.is_empty() {
+        Vector::new(
+            -(bbox.x.min + bbox.x.max) / 2.0,
+            -(bbox.y.min + bbox.y.max) / 2.0,
+            -(bbox.z.min + bbox.z.max) / 2.0,
+        )
+    } else {
+        Vector::default()
+    };
+    let matrix = options.transform * Matrix4::translation(offset) * up;
+    let normal_matrix = matrix
+        .inverse()
+        .ok_or(ObjError::SingularTransform)?
+        .transpose();
+
+    // Keyed by material id (`None` sorts first, so it is moved to the back below)
+    // and by which vertex attributes are present, since a mesh stores them for
+    // every vertex or not at all.
+    let mut groups: BTreeMap<(Option<usize>, bool, bool), TriangleMesh> = BTreeMap::new();
+    for m in models.iter() {
         let mesh = &m.mesh;
-        let mut mat: Arc<dyn Material> = Arc::new(Lambertian::new(Vector::new(0.0, 0.0, 0.0)));
-        let id = mesh.material_id;
-        if let Some(diffuse) = materials.clone()[id.unwrap()].diffuse {
-            mat = Arc::new(Lambertian::new(Vector::new(
-                diffuse[0] as f64,
-                diffuse[1] as f64,
-                diffuse[2] as f64,
-            )));
+        let material_id = mesh.material_id.filter(|&id| id < obj_materials.len());
+        let has_normals = options.subdivision.is_some() || !mesh.normals.is_empty();
+        let has_uvs = !mesh.texcoords.is_empty();
+        let group = groups
+            .entry((material_id, has_normals, has_uvs))
+            .or_insert_with(|| {
+                let obj_mat = material_id.map(|id| &obj_materials[id]);
+                let mat = obj_mat.map_or(default_mat.clone(), |m| m.mat.clone());
+                let mut triangle_mesh =
+                    TriangleMesh::new(Vec::new(), Vec::new(), Vec::new(), Vec::new(), mat);
+                triangle_mesh.normal_map = obj_mat.and_then(|m| m.normal_map.clone());
+                triangle_mesh
+            });
+        let base = group.positions.len() as u32;
+        if let Some(subdivision) = &options.subdivision {
+            let smooth = polygon_mesh(mesh, &matrix, options.flip_winding)
+                .subdivide(subdivision)
+                .to_triangle_mesh(group.mat.clone());
+            group.positions.extend(smooth.positions);
+            group.normals.extend(smooth.normals);
+            group.uvs.extend(smooth.uvs);
+            group
+                .indices
+                .extend(smooth.indices.iter().map(|f| f.map(|i| base + i)));
+            continue;
         }
-        if !mesh.face_arities.is_empty() {
-            let mut next_face = 0;
-            for f in 0..mesh.face_arities.len() {
-                let end = next_face + mesh.face_arities[f] as usize;
-                let face_indices: Vec<_> = mesh.indices[next_face..end].iter().collect();
-                next_face = end;
-                let mut point: [Vector; 3] = [Vector::new(0.0, 0.0, 0.0); 3];
-                let mut t = 0;
-                let p0 = *face_indices[0] as usize;
-                point[0] = Vector::new(
-                    mesh.positions[3 * p0] as f64 * scale,
-                    mesh.positions[3 * p0 + 1] as f64 * scale,
-                    mesh.positions[3 * p0 + 2] as f64 * scale,
-                );
-                for v in face_indices {
-                    t += 1;
-                    point[1] = point[2];
-                    point[2] = Vector::new(
-                        mesh.positions[3 * (*v as usize)] as f64 * scale,
-                        mesh.positions[3 * (*v as usize) + 1] as f64 * scale,
-                        mesh.positions[3 * (*v as usize) + 2] as f64 * scale,
-                    );
-                    if t >= 3 {
-                        val.add(Arc::new(Triangle::new(point[0], point[1], point[2], mat.clone())));
-                    }
-                }
+        group.positions.extend(
+            mesh.positions.chunks(3).map(|p| {
+                matrix.transform_point(Vector::new(p[0] as f64, p[1] as f64, p[2] as f64))
+            }),
+        );
+        // Reversing the winding turns the surface inside out, and the normals
+        // with it.
+        let facing = if options.flip_winding { -1.0 } else { 1.0 };
+        group.normals.extend(mesh.normals.chunks(3).map(|n| {
+            normal_matrix
+                .transform_vector(Vector::new(n[0] as f64, n[1] as f64, n[2] as f64))
+                .unit()
+                * facing
+        }));
+        group
+            .uvs
+            .extend(mesh.texcoords.chunks(2).map(|t| (t[0] as f64, t[1] as f64)));
+        group.indices.extend(mesh.indices.chunks(3).map(|f| {
+            if options.flip_winding {
+                [base + f[0], base + f[2], base + f[1]]
+            } else {
+                [base + f[0], base + f[1], base + f[2]]
             }
-        } else {
-            let mut point: [Vector; 3] = [Vector::new(0.0, 0.0, 0.0); 3];
-            let mut t = 0;
-            for v in &mesh.indices {
-                point[t] = Vector::new(
-                    mesh.positions[3 * (*v as usize)] as f64 * scale,
-                    mesh.positions[3 * (*v as usize) + 1] as f64 * scale,
-                    mesh.positions[3 * (*v as usize) + 2] as f64 * scale,
-                );
-                t += 1;
-                if t == 3 {
-                    val.add(Arc::new(Triangle::new(point[0], point[1], point[2], mat.clone())));
-                    t = 0;
-                }
-            }
-        }
+        }));
     }
+    let (unassigned, mut meshes): (Vec<_>, Vec<_>) = groups
+        .into_iter()
+        .partition(|((material_id, _, _), _)| material_id.is_none());
+    meshes.extend(unassigned);
+    let meshes: Vec<TriangleMesh> = meshes.into_iter().map(|(_, mesh)| mesh).collect();
     // for (i, m) in models.iter().enumerate() {
     //     let mesh = &m.mesh;
 
@@ -157,7 +340,206 @@ pub fn get_obj(obj_filename: &str, scale: f64) -> HittableList {
     //         println!("    material.{} = {}", k, v);
     //     }
     // }
-    let mut bvh_val = HittableList::new();
-    bvh_val.initialise(Arc::new(BvhNode::initialise(&mut val)));
-    bvh_val
+    Ok(match options.grouping {
+        MaterialGrouping::Merge => mesh_list(meshes),
+        MaterialGrouping::Split => {
+            let mut val = HittableList::new();
+            for mesh in meshes.into_iter().filter(|mesh| !mesh.indices.is_empty()) {
+                val.add(Arc::new(mesh.bvh()));
+            }
+            val
+        }
+    })
+}
+
+// Emission wins over everything else, then refraction (`Ni` together with a
+// dissolve or one of the glass illumination models), then a glossy lobe when
+// both `Ks` and `Ns` are given; partial `d` / `Tr` wraps the result in `Dissolve`.
+fn obj_material(m: &tobj::Material, dir: &Path) -> ObjMaterial {
+    let color = |c: [f32; 3]| Vector::new(c[0] as f64, c[1] as f64, c[2] as f64);
+    let normal_map = m
+        .normal_texture
+        .as_ref()
+        .or_else(|| m.unknown_param.get("norm"))
+        .and_then(|name| find_texture(dir, name))
+        .map(|path| {
+            Arc::new(NormalMap::image_new(&path, NormalMapConvention::OpenGl))
+                as Arc<dyn NormalModifier>
+        });
+    if let Some(emit) = m.emissive.map(color) {
+        if emit.x + emit.y + emit.z > 0.0 {
+            return ObjMaterial {
+                mat: Arc::new(DiffuseLight::color_new(emit)),
+                normal_map,
+            };
+        }
+    }
+    let opacity = m
+        .dissolve
+        .map(|d| d as f64)
+        .or_else(|| {
+            m.unknown_param
+                .get("Tr")
+                .and_then(|tr| tr.parse::<f64>().ok())
+                .map(|tr| 1.0 - tr)
+        })
+        .unwrap_or(1.0);
+    let ior = m.optical_density.unwrap_or(1.0) as f64;
+    let glass_illum = matches!(m.illumination_model, Some(4 | 6 | 7 | 9));
+    if ior > 1.0 && (opacity < 1.0 || glass_illum) {
+        return ObjMaterial {
+            mat: Arc::new(Dielectric::new(ior)),
+            normal_map,
+        };
+    }
+    let diffuse_color = m.diffuse.map(color).unwrap_or(Vector::new(0.5, 0.5, 0.5));
+    let diffuse: Arc<dyn Texture> = match m
+        .diffuse_texture
+        .as_ref()
+        .and_then(|name| find_texture(dir, name))
+    {
+        Some(path) => Arc::new(ImageTexture::new(&path)),
+        None => Arc::new(SolidColor::new(diffuse_color)),
+    };
+    let specular = m.specular.map(color).unwrap_or_default();
+    let mut mat: Arc<dyn Material> = match m.shininess {
+        Some(ns) if !specular.near_zero() => {
+            let fuzz = (2.0 / (ns.max(0.0) as f64 + 2.0)).sqrt();
+            Arc::new(Glossy::new(diffuse, specular, fuzz))
+        }
+        _ => Arc::new(Lambertian::arc_new(diffuse)),
+    };
+    if opacity < 1.0 {
+        mat = Arc::new(Dissolve::new(mat, opacity));
+    }
+    ObjMaterial { mat, normal_map }
+}
+
+// MTL texture statements may carry options (`-bm 0.5 file.png`) and are often
+// exported with absolute Windows paths, so try the whole statement and its last
+// word, each relative to the OBJ directory and then by file name alone.
+fn find_texture(dir: &Path, statement: &str) -> Option<String> {
+    let statement = statement.trim().replace('\\', "/");
+    let last_word = statement.split_whitespace().last()?.to_string();
+    let mut candidates: Vec<PathBuf> = Vec::new();
+    for name in [&statement, &last_word] {
+        candidates.push(dir.join(name));
+        if let Some(file_name) = Path::new(name).file_name() {
+            candidates.push(dir.join(file_name));
+        }
+    }
+    match candidates.iter().find(|path| path.is_file()) {
+        Some(path) => Some(path.to_string_lossy().into_owned()),
+        None => {
+            eprintln!(
+                "WARNING: texture '{}' not found, using the MTL color.",
+                statement
+            );
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittable;
+    use crate::interval::Interval;
+    use crate::ray::Ray;
+    use crate::rtweekend::INFINITY;
+
+    // A triangle in the z = 0 plane, counter-clockwise seen from +z, with
+    // normals along +z.
+    const TRIANGLE: &str = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//1 3//1\n";
+
+    fn write_obj(name: &str, obj: &str, mtl: Option<&str>) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        if let Some(mtl) = mtl {
+            std::fs::write(dir.join("scene.mtl"), mtl).unwrap();
+        }
+        std::fs::write(dir.join("scene.obj"), obj).unwrap();
+        dir.join("scene.obj")
+    }
+
+    fn bounds(list: &HittableList) -> AABB {
+        let mut bbox = AABB::default();
+        for object in list.objects.iter() {
+            bbox = AABB::box_new(&bbox, &object.bounding_box());
+        }
+        bbox
+    }
+
+    #[test]
+    fn flipped_normals_face_the_ray() {
+        let path = write_obj("obj_flip", TRIANGLE, None);
+        for flip_winding in [false, true] {
+            let options = ObjImportOptions {
+                flip_winding,
+                ..Default::default()
+            };
+            let list = load_obj(&path, &options).unwrap();
+            let r = Ray::new(
+                Vector::new(0.25, 0.25, 1.0),
+                Vector::new(0.0, 0.0, -1.0),
+                0.0,
+            );
+            let rec = list.hit(&r, &Interval::new(0.001, INFINITY)).unwrap();
+            assert!(rec.normal.dot(&r.direction) < 0.0);
+            // Flipped, the triangle is seen from behind.
+            assert_eq!(rec.front_face, !flip_winding);
+        }
+    }
+
+    #[test]
+    fn z_up_files_are_turned_y_up_and_recentered() {
+        let path = write_obj("obj_up_axis", "v 0 0 0\nv 2 0 0\nv 0 -1 4\nf 1 2 3\n", None);
+        let options = ObjImportOptions {
+            up_axis: UpAxis::Z,
+            ..Default::default()
+        };
+        // Boxes are padded by up to 5e-5 where the triangle lies flat.
+        let bbox = bounds(&load_obj(&path, &options).unwrap());
+        assert!((bbox.y.max - 4.0).abs() < 1e-3 && bbox.y.min.abs() < 1e-3);
+        assert!((bbox.z.max - 1.0).abs() < 1e-3);
+
+        let options = ObjImportOptions {
+            up_axis: UpAxis::Z,
+            recenter: true,
+            ..Default::default()
+        };
+        let bbox = bounds(&load_obj(&path, &options).unwrap());
+        assert!((bbox.x.min + 1.0).abs() < 1e-3 && (bbox.x.max - 1.0).abs() < 1e-3);
+        assert!((bbox.y.min + 2.0).abs() < 1e-3 && (bbox.y.max - 2.0).abs() < 1e-3);
+        assert!((bbox.z.min + bbox.z.max).abs() < 1e-3);
+    }
+
+    #[test]
+    fn split_grouping_gives_one_bvh_per_material_in_mtl_order() {
+        // Faces at x = 20, 10 and 5 using no material, b and a.
+        let obj = "mtllib scene.mtl\n\
+                   v 20 0 0\nv 21 0 0\nv 20 1 0\n\
+                   v 10 0 0\nv 11 0 0\nv 10 1 0\n\
+                   v 5 0 0\nv 6 0 0\nv 5 1 0\n\
+                   f 1 2 3\n\
+                   usemtl b\nf 4 5 6\n\
+                   usemtl a\nf 7 8 9\n";
+        let mtl = "newmtl a\nKd 1 0 0\nnewmtl b\nKd 0 1 0\n";
+        let path = write_obj("obj_grouping", obj, Some(mtl));
+
+        let merged = load_obj(&path, &Default::default()).unwrap();
+        assert_eq!(merged.objects.len(), 1);
+
+        let options = ObjImportOptions {
+            grouping: MaterialGrouping::Split,
+            ..Default::default()
+        };
+        let split = load_obj(&path, &options).unwrap();
+        let starts: Vec<f64> = split
+            .objects
+            .iter()
+            .map(|object| object.bounding_box().x.min.round())
+            .collect();
+        assert_eq!(starts, vec![5.0, 10.0, 20.0]);
+    }
 }
@@ -1,6 +1,6 @@
 use crate::vec3::Vector;
 
-#[derive(Default)]
+#[derive(Copy, Clone, Default)]
 pub struct Onb {
     pub axis: [Vector; 3],
 }
@@ -25,4 +25,12 @@ impl Onb {
     pub fn vec_local(&self, a: Vector) -> Vector {
         self.axis[0] * a.x + self.axis[1] * a.y + self.axis[2] * a.z
     }
+    // Inverse of `vec_local`: the components of a world vector in this basis.
+    pub fn coords(&self, a: Vector) -> Vector {
+        Vector::new(
+            a.dot(&self.axis[0]),
+            a.dot(&self.axis[1]),
+            a.dot(&self.axis[2]),
+        )
+    }
 }
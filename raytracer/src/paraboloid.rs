@@ -0,0 +1,213 @@
+use crate::aabb::AABB;
+use crate::disk::Disk;
+use crate::hittable::{area_pdf_value, HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::rtweekend::{random_double, solve_quadratic, PI};
+use crate::vec3::Vector;
+
+use std::sync::Arc;
+
+// A paraboloid bowl with its vertex at `base`, opening along `axis` to a rim of
+// `radius` at `base + axis`, optionally closed by a disk over the rim. u runs
+// around the axis and v from the vertex to the rim. The bowl bulges past the
+// cone from vertex to rim, so it is bounded by the enclosing cylinder.
+pub struct Paraboloid {
+    pub base: Vector,
+    pub frame: Onb,
+    pub height: f64,
+    pub radius: f64,
+    pub caps: Vec<Disk>,
+    pub mat: Arc<dyn Material>,
+    pub bbox: AABB,
+    pub side_area: f64,
+    pub area: f64,
+}
+
+impl Paraboloid {
+    pub fn new(
+        base: Vector,
+        axis: Vector,
+        radius: f64,
+        capped: bool,
+        mat: Arc<dyn Material>,
+    ) -> Self {
+        let mut frame: Onb = Default::default();
+        frame.build_from_w(axis);
+        let height = axis.length();
+        let top = base + axis;
+        let caps = if capped {
+            vec![Disk::new(top, axis, radius, mat.clone())]
+        } else {
+            Vec::new()
+        };
+        let mut val = Self {
+            base,
+            frame,
+            height,
+            radius,
+            caps,
+            mat,
+            bbox: AABB::box_new(
+                &Disk::circle_bounding_box(base, frame.axis[2], radius),
+                &Disk::circle_bounding_box(top, frame.axis[2], radius),
+            ),
+            side_area: 0.0,
+            area: 0.0,
+        };
+        val.side_area = match val.slope() {
+            Some(k) => 2.0 * PI / (3.0 * k * k) * ((1.0 + k * k * radius * radius).powf(1.5) - 1.0),
+            None => PI * radius * radius,
+        };
+        val.area = val.side_area + val.caps.iter().map(|cap| cap.area).sum::<f64>();
+        val
+    }
+    // The surface is z = (k / 2) (x^2 + y^2); `None` when it is too flat to tell
+    // apart from a disk.
+    fn slope(&self) -> Option<f64> {
+        let k = 2.0 * self.height / (self.radius * self.radius);
+        if k * self.radius < 1e-6 {
+            None
+        } else {
+            Some(k)
+        }
+    }
+    fn hit_side(&self, r: &Ray, ray_t: &Interval) -> Option<HitRecord> {
+        let o = self.frame.coords(r.origin - self.base);
+        let d = self.frame.coords(r.direction);
+        let s = self.height / (self.radius * self.radius);
+        let a = s * (d.x * d.x + d.y * d.y);
+        let b = 2.0 * s * (o.x * d.x + o.y * d.y) - d.z;
+        let c = s * (o.x * o.x + o.y * o.y) - o.z;
+        let (t0, t1) = solve_quadratic(a, b, c)?;
+        for t in [t0, t1] {
+            let z = o.z + t * d.z;
+            if !ray_t.surrounds(t) || z < 0.0 || z > self.height {
+                continue;
+            }
+            let x = o.x + t * d.x;
+            let y = o.y + t * d.y;
+            let outward_normal = self.frame.local(2.0 * s * x, 2.0 * s * y, -1.0).unit();
+            let mut rec = HitRecord::new(r.at(t), Vector::default(), t, false);
+            rec.set_face_normal(r, &outward_normal);
+            rec.u = (y.atan2(x) + PI) / (2.0 * PI);
+            rec.v = z / self.height;
+            // The radius grows as the square root of v, so v has no derivative
+            // at the vertex, where u has none either.
+            rec.dpdu = self.frame.local(-y, x, 0.0) * (2.0 * PI);
+            if z > 0.0 {
+                let spread = self.height / (2.0 * z);
+                rec.dpdv = self.frame.local(x * spread, y * spread, self.height);
+            }
+            rec.mat = Some(self.mat.clone());
+            return Some(rec);
+        }
+        None
+    }
+}
+
+impl Hittable for Paraboloid {
+    fn hit(&self, r: &Ray, ray_t: &Interval) -> Option<HitRecord> {
+        let mut closest = self.hit_side(r, ray_t);
+        for cap in self.caps.iter() {
+            let t_max = closest.as_ref().map_or(ray_t.max, |rec| rec.t);
+            if let Some(rec) = cap.hit(r, &Interval::new(ray_t.min, t_max)) {
+                closest = Some(rec);
+            }
+        }
+        closest
+    }
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+    fn pdf_value(&self, origin: Vector, direction: Vector) -> f64 {
+        area_pdf_value(self, self.area, origin, direction)
+    }
+    // The side area within distance rho of the axis is proportional to
+    // (1 + k^2 rho^2)^(3/2) - 1, which inverts in closed form.
+    fn random(&self, origin: Vector) -> Vector {
+        let mut pick = random_double() * self.area - self.side_area;
+        if pick >= 0.0 {
+            for cap in self.caps.iter() {
+                if pick < cap.area {
+                    return cap.random(origin);
+                }
+                pick -= cap.area;
+            }
+        }
+        let xi = random_double();
+        let rho = match self.slope() {
+            Some(k) => {
+                let rim = (1.0 + k * k * self.radius * self.radius).powf(1.5);
+                ((1.0 + xi * (rim - 1.0)).powf(2.0 / 3.0) - 1.0)
+                    .max(0.0)
+                    .sqrt()
+                    / k
+            }
+            None => self.radius * xi.sqrt(),
+        };
+        let phi = 2.0 * PI * random_double();
+        let z = self.height * rho * rho / (self.radius * self.radius);
+        let p = self.frame.local(rho * phi.cos(), rho * phi.sin(), z);
+        self.base + p - origin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::assert_pdf_matches_random;
+    use crate::material::Lambertian;
+    use crate::rtweekend::INFINITY;
+
+    // The bowl y = 2 (x^2 + z^2) up to a rim of radius 1 at y = 2.
+    fn bowl(capped: bool) -> Paraboloid {
+        let mat: Arc<dyn Material> = Arc::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5)));
+        Paraboloid::new(
+            Vector::default(),
+            Vector::new(0.0, 2.0, 0.0),
+            1.0,
+            capped,
+            mat,
+        )
+    }
+
+    #[test]
+    fn side_hits_face_away_from_the_inside() {
+        let r = Ray::new(Vector::new(5.0, 0.5, 0.0), Vector::new(-1.0, 0.0, 0.0), 0.0);
+        let rec = bowl(false)
+            .hit(&r, &Interval::new(0.001, INFINITY))
+            .unwrap();
+        assert!((rec.t - 4.5).abs() < 1e-12);
+        assert!(rec.front_face);
+        assert!((rec.normal - Vector::new(2.0, -1.0, 0.0).unit()).near_zero());
+        // Looking down into the open bowl the vertex is seen from inside.
+        let r = Ray::new(Vector::new(0.0, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0), 0.0);
+        let rec = bowl(false)
+            .hit(&r, &Interval::new(0.001, INFINITY))
+            .unwrap();
+        assert!((rec.t - 5.0).abs() < 1e-12);
+        assert!(!rec.front_face);
+        assert!((rec.normal - Vector::new(0.0, 1.0, 0.0)).near_zero());
+    }
+
+    #[test]
+    fn cap_closes_the_rim() {
+        let r = Ray::new(Vector::new(0.5, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0), 0.0);
+        let rec = bowl(true).hit(&r, &Interval::new(0.001, INFINITY)).unwrap();
+        assert!((rec.t - 3.0).abs() < 1e-12);
+        assert!((rec.normal - Vector::new(0.0, 1.0, 0.0)).near_zero());
+        let rec = bowl(false)
+            .hit(&r, &Interval::new(0.001, INFINITY))
+            .unwrap();
+        assert!((rec.t - 4.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn pdf_matches_random() {
+        assert_pdf_matches_random(&bowl(true), Vector::new(2.0, 3.0, 1.5));
+        assert_pdf_matches_random(&bowl(false), Vector::new(0.5, 4.0, 0.0));
+    }
+}
@@ -0,0 +1,472 @@
+use crate::material::{Lambertian, Material};
+use crate::mesh::{smooth_normals, TriangleMesh};
+use crate::vec3::Vector;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub enum PlyError {
+    Io(std::io::Error),
+    InvalidHeader(String),
+    InvalidData(String),
+}
+
+impl fmt::Display for PlyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlyError::Io(e) => write!(f, "failed to read PLY file: {}", e),
+            PlyError::InvalidHeader(msg) => write!(f, "invalid PLY header: {}", msg),
+            PlyError::InvalidData(msg) => write!(f, "invalid PLY data: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for PlyError {}
+
+impl From<std::io::Error> for PlyError {
+    fn from(e: std::io::Error) -> Self {
+        PlyError::Io(e)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Copy, Clone)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Result<Self, PlyError> {
+        match name {
+            "char" | "int8" => Ok(Scalar::I8),
+            "uchar" | "uint8" => Ok(Scalar::U8),
+            "short" | "int16" => Ok(Scalar::I16),
+            "ushort" | "uint16" => Ok(Scalar::U16),
+            "int" | "int32" => Ok(Scalar::I32),
+            "uint" | "uint32" => Ok(Scalar::U32),
+            "float" | "float32" => Ok(Scalar::F32),
+            "double" | "float64" => Ok(Scalar::F64),
+            _ => Err(PlyError::InvalidHeader(format!("unknown type '{}'", name))),
+        }
+    }
+    fn size(&self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+}
+
+struct Property {
+    name: String,
+    // `Some(count type)` for list properties.
+    list: Option<Scalar>,
+    scalar: Scalar,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+// Values are handed out as f64 whatever the file stores, which is exact for
+// every PLY type and for any index below 2^53.
+struct Reader<'a> {
+    format: Format,
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn read(&mut self, scalar: Scalar) -> Result<f64, PlyError> {
+        if self.format == Format::Ascii {
+            return self.read_ascii();
+        }
+        let size = scalar.size();
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + size)
+            .ok_or_else(|| PlyError::InvalidData("unexpected end of file".to_string()))?;
+        self.pos += size;
+        let mut buf = [0u8; 8];
+        buf[..size].copy_from_slice(bytes);
+        if self.format == Format::BinaryBigEndian {
+            buf[..size].reverse();
+        }
+        Ok(match scalar {
+            Scalar::I8 => buf[0] as i8 as f64,
+            Scalar::U8 => buf[0] as f64,
+            Scalar::I16 => i16::from_le_bytes([buf[0], buf[1]]) as f64,
+            Scalar::U16 => u16::from_le_bytes([buf[0], buf[1]]) as f64,
+            Scalar::I32 => i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            Scalar::U32 => u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            Scalar::F32 => f32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            Scalar::F64 => f64::from_le_bytes(buf),
+        })
+    }
+    fn read_ascii(&mut self) -> Result<f64, PlyError> {
+        while self.pos < self.data.len() && self.data[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+        let start = self.pos;
+        while self.pos < self.data.len() && !self.data[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err(PlyError::InvalidData("unexpected end of file".to_string()));
+        }
+        let token = String::from_utf8_lossy(&self.data[start..self.pos]);
+        token
+            .parse::<f64>()
+            .map_err(|_| PlyError::InvalidData(format!("'{}' is not a number", token)))
+    }
+}
+
+fn parse_header(data: &[u8]) -> Result<(Format, Vec<Element>, usize), PlyError> {
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    let mut pos = 0;
+    let mut first = true;
+    loop {
+        let end = data[pos..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| PlyError::InvalidHeader("missing end_header".to_string()))?;
+        let line = String::from_utf8_lossy(&data[pos..pos + end]).to_string();
+        pos += end + 1;
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if first {
+            if words != ["ply"] {
+                return Err(PlyError::InvalidHeader("missing 'ply' magic".to_string()));
+            }
+            first = false;
+            continue;
+        }
+        match words.as_slice() {
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => {
+                        return Err(PlyError::InvalidHeader(format!(
+                            "unknown format '{}'",
+                            name
+                        )))
+                    }
+                })
+            }
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| {
+                    PlyError::InvalidHeader(format!("bad count for element '{}'", name))
+                })?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count_type, item_type, name] => elements
+                .last_mut()
+                .ok_or_else(|| PlyError::InvalidHeader("property before element".to_string()))?
+                .properties
+                .push(Property {
+                    name: name.to_string(),
+                    list: Some(Scalar::parse(count_type)?),
+                    scalar: Scalar::parse(item_type)?,
+                }),
+            ["property", scalar, name] => elements
+                .last_mut()
+                .ok_or_else(|| PlyError::InvalidHeader("property before element".to_string()))?
+                .properties
+                .push(Property {
+                    name: name.to_string(),
+                    list: None,
+                    scalar: Scalar::parse(scalar)?,
+                }),
+            ["end_header"] => break,
+            _ => {
+                return Err(PlyError::InvalidHeader(format!(
+                    "unexpected line '{}'",
+                    line
+                )))
+            }
+        }
+    }
+    let format = format.ok_or_else(|| PlyError::InvalidHeader("missing format".to_string()))?;
+    Ok((format, elements, pos))
+}
+
+// Reads positions, normals (`nx ny nz`), UVs (`u v`, `s t` or `texture_u
+// texture_v`) and colors (`red green blue`, integer channels scaled to [0, 1])
+// from the `vertex` element and fan-triangulates every polygon of the `face`
+// element; any other element is skipped. Colored meshes get a white
+// Lambertian so the vertex colors come through as the albedo, others a gray one.
+pub fn load_ply<P: AsRef<Path>>(path: P) -> Result<TriangleMesh, PlyError> {
+    parse_ply(&std::fs::read(path)?)
+}
+
+fn parse_ply(data: &[u8]) -> Result<TriangleMesh, PlyError> {
+    let (format, elements, header_len) = parse_header(data)?;
+    let mut reader = Reader {
+        format,
+        data: &data[header_len..],
+        pos: 0,
+    };
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut colors = Vec::new();
+    let mut indices = Vec::new();
+    for element in elements.iter() {
+        let find = |names: &[&str]| {
+            element
+                .properties
+                .iter()
+                .position(|p| p.list.is_none() && names.contains(&p.name.as_str()))
+        };
+        let position = [find(&["x"]), find(&["y"]), find(&["z"])];
+        let normal = [find(&["nx"]), find(&["ny"]), find(&["nz"])];
+        let uv = [
+            find(&["u", "s", "texture_u"]),
+            find(&["v", "t", "texture_v"]),
+        ];
+        let color = [
+            find(&["red", "diffuse_red"]),
+            find(&["green", "diffuse_green"]),
+            find(&["blue", "diffuse_blue"]),
+        ];
+        let face_list = element.properties.iter().position(|p| {
+            p.list.is_some() && (p.name == "vertex_indices" || p.name == "vertex_index")
+        });
+        let is_vertex = element.name == "vertex";
+        let is_face = element.name == "face";
+        let mut values = vec![0.0; element.properties.len()];
+        let mut polygon = Vec::new();
+        for _ in 0..element.count {
+            for (k, property) in element.properties.iter().enumerate() {
+                match property.list {
+                    None => values[k] = reader.read(property.scalar)?,
+                    Some(count_type) => {
+                        let count = reader.read(count_type)? as usize;
+                        let keep = is_face && face_list == Some(k);
+                        if keep {
+                            polygon.clear();
+                        }
+                        for _ in 0..count {
+                            let item = reader.read(property.scalar)?;
+                            if keep {
+                                // Indices are read signed, a cast would wrap negative ones.
+                                if item < 0.0 || item.fract() != 0.0 || item > u32::MAX as f64 {
+                                    return Err(PlyError::InvalidData(format!(
+                                        "face index {} is not a vertex number",
+                                        item
+                                    )));
+                                }
+                                polygon.push(item as u32);
+                            }
+                        }
+                    }
+                }
+            }
+            if is_vertex {
+                let get = |index: Option<usize>| index.map(|k| values[k]);
+                let point = |k: [Option<usize>; 3]| match (get(k[0]), get(k[1]), get(k[2])) {
+                    (Some(x), Some(y), Some(z)) => Some(Vector::new(x, y, z)),
+                    _ => None,
+                };
+                positions.push(
+                    point(position).ok_or_else(|| {
+                        PlyError::InvalidData("vertex without x, y, z".to_string())
+                    })?,
+                );
+                if let Some(n) = point(normal) {
+                    normals.push(n);
+                }
+                if let (Some(u), Some(v)) = (get(uv[0]), get(uv[1])) {
+                    uvs.push((u, v));
+                }
+                if let Some(c) = point(color) {
+                    let integer = !matches!(
+                        element.properties[color[0].unwrap()].scalar,
+                        Scalar::F32 | Scalar::F64
+                    );
+                    colors.push(if integer { c / 255.0 } else { c });
+                }
+            }
+            if is_face && polygon.len() >= 3 {
+                for k in 1..polygon.len() - 1 {
+                    indices.push([polygon[0], polygon[k], polygon[k + 1]]);
+                }
+            }
+        }
+    }
+    if let Some(face) = indices
+        .iter()
+        .flatten()
+        .find(|&&i| i as usize >= positions.len())
+    {
+        return Err(PlyError::InvalidData(format!(
+            "face index {} out of range for {} vertices",
+            face,
+            positions.len()
+        )));
+    }
+    // Scans often carry zero normals on unreferenced or degenerate vertices, those
+    // take the area-weighted normal of their faces rather than shading with NaNs.
+    let normals = if normals.iter().any(|n| n.near_zero()) {
+        let smooth = smooth_normals(&positions, &indices);
+        normals
+            .iter()
+            .zip(smooth)
+            .map(|(n, s)| if n.near_zero() { s } else { n.unit() })
+            .collect()
+    } else {
+        normals.iter().map(|n| n.unit()).collect()
+    };
+    let mat: Arc<dyn Material> = if colors.is_empty() {
+        Arc::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5)))
+    } else {
+        Arc::new(Lambertian::new(Vector::new(1.0, 1.0, 1.0)))
+    };
+    let mut mesh = TriangleMesh::new(positions, normals, uvs, indices, mat);
+    mesh.colors = colors;
+    Ok(mesh)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASCII: &str = "ply
+format ascii 1.0
+comment a unit square with one zero normal
+element vertex 4
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 0 0 1 255 0 0
+1 0 0 0 0 0 0 255 0
+1 1 0 0 0 2 0 0 255
+0 1 0 0 0 1 51 51 51
+4 0 1 2 3
+";
+
+    // The same square as positions and a quad with a ushort count and uint
+    // indices, followed by an element the importer skips.
+    fn binary(format: &str, big_endian: bool) -> Vec<u8> {
+        let header = format!(
+            "ply\nformat {} 1.0\nelement vertex 4\nproperty float x\nproperty float y\n\
+             property double z\nelement face 1\nproperty list ushort uint vertex_indices\n\
+             element edge 1\nproperty int vertex1\nproperty int vertex2\nend_header\n",
+            format
+        );
+        let mut data = header.into_bytes();
+        let push = |bytes: &mut [u8], data: &mut Vec<u8>| {
+            if big_endian {
+                bytes.reverse();
+            }
+            data.extend_from_slice(bytes);
+        };
+        for (x, y) in [(0.0f32, 0.0f32), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)] {
+            push(&mut x.to_le_bytes(), &mut data);
+            push(&mut y.to_le_bytes(), &mut data);
+            push(&mut 0.5f64.to_le_bytes(), &mut data);
+        }
+        push(&mut 4u16.to_le_bytes(), &mut data);
+        for i in 0u32..4 {
+            push(&mut i.to_le_bytes(), &mut data);
+        }
+        push(&mut 0i32.to_le_bytes(), &mut data);
+        push(&mut 1i32.to_le_bytes(), &mut data);
+        data
+    }
+
+    fn assert_square(mesh: &TriangleMesh, z: f64) {
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 2, 3]]);
+        let p = mesh.positions[2];
+        assert!(p.x == 1.0 && p.y == 1.0 && p.z == z);
+    }
+
+    #[test]
+    fn ascii_reads_polygons_normals_and_colors() {
+        let mesh = parse_ply(ASCII.as_bytes()).unwrap();
+        assert_square(&mesh, 0.0);
+        // The zero normal takes the face normal, the others are only normalized.
+        assert_eq!(mesh.normals.len(), 4);
+        for n in mesh.normals.iter() {
+            assert!((n.z - 1.0).abs() < 1e-12 && n.x == 0.0 && n.y == 0.0);
+        }
+        assert_eq!(mesh.colors.len(), 4);
+        assert!((mesh.colors[0].x - 1.0).abs() < 1e-12);
+        assert!((mesh.colors[3].y - 0.2).abs() < 1e-12);
+    }
+
+    #[test]
+    fn binary_reads_both_byte_orders() {
+        let little = parse_ply(&binary("binary_little_endian", false)).unwrap();
+        let big = parse_ply(&binary("binary_big_endian", true)).unwrap();
+        assert_square(&little, 0.5);
+        assert_square(&big, 0.5);
+        assert!(little.normals.is_empty() && little.colors.is_empty());
+    }
+
+    #[test]
+    fn truncated_and_out_of_range_data_is_rejected() {
+        let data = binary("binary_little_endian", false);
+        assert!(matches!(
+            parse_ply(&data[..data.len() - 3]),
+            Err(PlyError::InvalidData(_))
+        ));
+        for face in ["3 0 1 7", "3 0 -1 2", "3 0 1.5 2", "3 0 4294967296 2"] {
+            let bad_index = ASCII.replace("4 0 1 2 3", face);
+            assert!(
+                matches!(
+                    parse_ply(bad_index.as_bytes()),
+                    Err(PlyError::InvalidData(_))
+                ),
+                "{}",
+                face
+            );
+        }
+        // With signed indices in a binary file, -1 must not wrap to 2^32 - 1.
+        let data = binary("binary_little_endian", false);
+        let body = data.windows(11).position(|w| w == b"end_header\n").unwrap() + 11;
+        let header = std::str::from_utf8(&data[..body]).unwrap();
+        let mut signed = header.replace("ushort uint", "ushort int").into_bytes();
+        signed.extend_from_slice(&data[body..]);
+        // The last index is followed by the two ints of the edge.
+        let last = signed.len() - 12;
+        signed[last..last + 4].copy_from_slice(&(-1i32).to_le_bytes());
+        assert!(matches!(parse_ply(&signed), Err(PlyError::InvalidData(_))));
+        assert!(matches!(
+            parse_ply(b"ply\nformat ascii 1.0\n"),
+            Err(PlyError::InvalidHeader(_))
+        ));
+    }
+}
@@ -0,0 +1,180 @@
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+
+use crate::camera::Camera;
+use crate::vec3::Vector;
+
+// Presets are stored as small text files:
+//
+//     # comment
+//     [desk]
+//     lookfrom 800 450 -800
+//     lookat 800 450 0
+//     vup 0 1 0
+//     vfov 40
+//     focus_dist 10
+//     defocus_angle 0
+//     roll 0
+//
+// Keys that are left out keep the value of `Camera::default()`.
+#[derive(Clone)]
+pub struct CameraPreset {
+    pub name: String,
+    pub lookfrom: Vector,
+    pub lookat: Vector,
+    pub vup: Vector,
+    pub vfov: f64,
+    pub focus_dist: f64,
+    pub defocus_angle: f64,
+    pub roll: f64,
+}
+
+impl CameraPreset {
+    pub fn new(name: &str, cam: &Camera) -> Self {
+        Self {
+            name: name.to_string(),
+            lookfrom: cam.lookfrom,
+            lookat: cam.lookat,
+            vup: cam.vup,
+            vfov: cam.vfov,
+            focus_dist: cam.focus_dist,
+            defocus_angle: cam.defocus_angle,
+            roll: cam.roll,
+        }
+    }
+    pub fn apply(&self, cam: &mut Camera) {
+        cam.lookfrom = self.lookfrom;
+        cam.lookat = self.lookat;
+        cam.vup = self.vup;
+        cam.vfov = self.vfov;
+        cam.focus_dist = self.focus_dist;
+        cam.defocus_angle = self.defocus_angle;
+        cam.roll = self.roll;
+    }
+    pub fn camera(&self) -> Camera {
+        let mut cam: Camera = Default::default();
+        self.apply(&mut cam);
+        cam
+    }
+}
+
+pub fn load_presets(filename: &str) -> Result<Vec<CameraPreset>> {
+    let text = fs::read_to_string(filename)?;
+    let mut presets: Vec<CameraPreset> = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            let name = line[1..line.len() - 1].trim();
+            presets.push(CameraPreset::new(name, &Camera::default()));
+            continue;
+        }
+        let invalid = |message: &str| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("{}:{}: {}", filename, number + 1, message),
+            )
+        };
+        let preset = presets
+            .last_mut()
+            .ok_or_else(|| invalid("setting before the first [name] line"))?;
+        let mut words = line.split_whitespace();
+        let key = words.next().unwrap_or_default();
+        let values = words
+            .map(|w| w.parse::<f64>())
+            .collect::<std::result::Result<Vec<f64>, _>>()
+            .map_err(|_| invalid("expected numbers"))?;
+        let scalar = || {
+            if values.len() == 1 {
+                Ok(values[0])
+            } else {
+                Err(invalid("expected one number"))
+            }
+        };
+        let vector = || {
+            if values.len() == 3 {
+                Ok(Vector::new(values[0], values[1], values[2]))
+            } else {
+                Err(invalid("expected three numbers"))
+            }
+        };
+        match key {
+            "lookfrom" => preset.lookfrom = vector()?,
+            "lookat" => preset.lookat = vector()?,
+            "vup" => preset.vup = vector()?,
+            "vfov" => preset.vfov = scalar()?,
+            "focus_dist" => preset.focus_dist = scalar()?,
+            "defocus_angle" => preset.defocus_angle = scalar()?,
+            "roll" => preset.roll = scalar()?,
+            _ => return Err(invalid(&format!("unknown key '{}'", key))),
+        }
+    }
+    Ok(presets)
+}
+
+pub fn save_presets(filename: &str, presets: &[CameraPreset]) -> Result<()> {
+    let mut text = String::new();
+    for preset in presets {
+        text += &format!("[{}]\n", preset.name);
+        for (key, v) in [
+            ("lookfrom", preset.lookfrom),
+            ("lookat", preset.lookat),
+            ("vup", preset.vup),
+        ] {
+            text += &format!("{} {} {} {}\n", key, v.x, v.y, v.z);
+        }
+        text += &format!("vfov {}\n", preset.vfov);
+        text += &format!("focus_dist {}\n", preset.focus_dist);
+        text += &format!("defocus_angle {}\n", preset.defocus_angle);
+        text += &format!("roll {}\n\n", preset.roll);
+    }
+    if let Some(prefix) = std::path::Path::new(filename).parent() {
+        fs::create_dir_all(prefix)?;
+    }
+    fs::write(filename, text)
+}
+
+pub fn find_preset<'a>(presets: &'a [CameraPreset], name: &str) -> Option<&'a CameraPreset> {
+    presets.iter().find(|preset| preset.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_presets_load_back_unchanged() {
+        let mut desk = CameraPreset::new(
+            "desk",
+            &Camera::angle_new(Vector::new(1.5, -2.0, 0.25), 30.0, -10.0, 5.0),
+        );
+        desk.vfov = 35.5;
+        desk.defocus_angle = 0.6;
+        let top = CameraPreset::new(
+            "top view",
+            &Camera::direction_new(Vector::new(0.0, 10.0, 0.0), Vector::new(0.0, -1.0, 0.0)),
+        );
+        let filename = std::env::temp_dir()
+            .join(format!("presets_{}", std::process::id()))
+            .join("cameras.txt");
+        let filename = filename.to_str().unwrap();
+        save_presets(filename, &[desk.clone(), top.clone()]).unwrap();
+        let loaded = load_presets(filename).unwrap();
+        fs::remove_file(filename).unwrap();
+
+        assert_eq!(loaded.len(), 2);
+        for (a, b) in [desk, top].iter().zip(loaded.iter()) {
+            assert_eq!(a.name, b.name);
+            assert!((a.lookfrom - b.lookfrom).near_zero());
+            assert!((a.lookat - b.lookat).near_zero());
+            assert!((a.vup - b.vup).near_zero());
+            assert_eq!(a.vfov, b.vfov);
+            assert_eq!(a.focus_dist, b.focus_dist);
+            assert_eq!(a.defocus_angle, b.defocus_angle);
+            assert_eq!(a.roll, b.roll);
+        }
+        assert!(find_preset(&loaded, "top view").is_some());
+    }
+}
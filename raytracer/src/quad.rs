@@ -6,6 +6,7 @@ use crate::hittable::Hittable;
 use crate::hittable_list::HittableList;
 use crate::interval::Interval;
 use crate::material::Material;
+use crate::mesh::TriangleMesh;
 use crate::ray::Ray;
 use crate::rtweekend::random_double;
 use crate::rtweekend::INFINITY;
@@ -59,6 +60,31 @@ impl Quad {
         val.v = b;
         Some(val)
     }
+    // The same surface as a grid of `resolution` by `resolution` cells, with the
+    // UVs `hit` would report, for instance to feed `displacement::displace`.
+    pub fn tessellate(&self, resolution: u32) -> TriangleMesh {
+        let n = resolution.max(1);
+        let mut positions = Vec::new();
+        let mut uvs = Vec::new();
+        for j in 0..=n {
+            for i in 0..=n {
+                let (a, b) = (i as f64 / n as f64, j as f64 / n as f64);
+                positions.push(self.q + self.u * a + self.v * b);
+                uvs.push((a, b));
+            }
+        }
+        let mut indices = Vec::new();
+        for j in 0..n {
+            for i in 0..n {
+                let corner = j * (n + 1) + i;
+                let (a, b, c, d) = (corner, corner + 1, corner + n + 2, corner + n + 1);
+                indices.push([a, b, c]);
+                indices.push([a, c, d]);
+            }
+        }
+        let normals = vec![self.normal; positions.len()];
+        TriangleMesh::new(positions, normals, uvs, indices, self.mat.clone())
+    }
 }
 
 impl Hittable for Quad {
@@ -80,6 +106,8 @@ impl Hittable for Quad {
             val.p = intersection;
             val.mat = Some(self.mat.clone());
             val.set_face_normal(r, &self.normal);
+            val.dpdu = self.u;
+            val.dpdv = self.v;
             Some(val)
         } else {
             None
@@ -0,0 +1,289 @@
+use crate::aabb::AABB;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::sphere::Sphere;
+use crate::vec3::Vector;
+
+use std::sync::Arc;
+
+// Real roots of c[0] + c[1] t + ... + c[4] t^4 inside `interval`, ascending.
+// The roots of the derivative split the interval into monotonic pieces, each
+// holding at most one root, which is then bracketed and polished. Unlike the
+// closed-form Ferrari solution this keeps full precision for the nearly
+// degenerate quartics that grazing rays produce. A turning point within
+// rounding error of zero counts as a root, so rays that only touch the surface,
+// along a silhouette, still hit it.
+pub fn solve_quartic(c: [f64; 5], interval: &Interval) -> Vec<f64> {
+    polynomial_roots(&c, interval.min, interval.max)
+}
+
+fn polynomial_roots(c: &[f64], min: f64, max: f64) -> Vec<f64> {
+    let degree = match c.iter().rposition(|&x| x != 0.0) {
+        Some(degree) if degree > 0 => degree,
+        _ => return Vec::new(),
+    };
+    let c = &c[..=degree];
+    if degree == 1 {
+        let t = -c[0] / c[1];
+        return if min <= t && t <= max {
+            vec![t]
+        } else {
+            Vec::new()
+        };
+    }
+    // No root lies outside the Cauchy bound, which also makes infinite intervals finite.
+    let bound = 1.0
+        + c[..degree]
+            .iter()
+            .map(|x| (x / c[degree]).abs())
+            .fold(0.0, f64::max);
+    let (min, max) = (min.max(-bound), max.min(bound));
+    if min > max {
+        return Vec::new();
+    }
+    let derivative: Vec<f64> = (1..=degree).map(|k| c[k] * k as f64).collect();
+    let turning = polynomial_roots(&derivative, min, max);
+    let mut points = vec![(min, evaluate(c, min).0)];
+    for &t in turning.iter() {
+        // The rounding error of Horner's scheme is bounded by a small multiple of
+        // the sum of the terms' magnitudes.
+        let magnitude = evaluate(&c.iter().map(|x| x.abs()).collect::<Vec<_>>(), t.abs()).0;
+        let f = evaluate(c, t).0;
+        points.push((t, if f.abs() <= 1e-12 * magnitude { 0.0 } else { f }));
+    }
+    points.push((max, evaluate(c, max).0));
+
+    let mut roots = Vec::new();
+    if points[0].1 == 0.0 {
+        roots.push(min);
+    }
+    for pair in points.windows(2) {
+        let ((a, fa), (b, fb)) = (pair[0], pair[1]);
+        if fb == 0.0 {
+            if b > a {
+                roots.push(b);
+            }
+        } else if fa != 0.0 && (fa < 0.0) != (fb < 0.0) {
+            roots.push(refine(c, a, b, fa < 0.0));
+        }
+    }
+    roots
+}
+
+// Newton steps, falling back to bisection whenever a step leaves the bracket.
+fn refine(c: &[f64], mut a: f64, mut b: f64, negative_at_a: bool) -> f64 {
+    let mut x = 0.5 * (a + b);
+    for _ in 0..100 {
+        let (f, df) = evaluate(c, x);
+        if f == 0.0 {
+            return x;
+        }
+        if (f < 0.0) == negative_at_a {
+            a = x;
+        } else {
+            b = x;
+        }
+        let newton = x - f / df;
+        let next = if newton > a && newton < b {
+            newton
+        } else {
+            0.5 * (a + b)
+        };
+        if (next - x).abs() <= 1e-14 * (1.0 + x.abs()) {
+            return next;
+        }
+        x = next;
+    }
+    x
+}
+
+// Horner evaluation of the polynomial and its derivative.
+fn evaluate(c: &[f64], t: f64) -> (f64, f64) {
+    let mut f = 0.0;
+    let mut df = 0.0;
+    for &x in c.iter().rev() {
+        df = df * t + f;
+        f = f * t + x;
+    }
+    (f, df)
+}
+
+// The implicit surface sum(coefficient * x^i y^j z^k) = 0 of total degree at
+// most four, such as a torus, Steiner surface or Kummer quartic. Such surfaces
+// are often unbounded, so only the part inside `bbox` is rendered. UVs are the
+// spherical coordinates around the center of the box. `new` reports and
+// returns None for a term of higher degree.
+pub struct QuarticSurface {
+    pub terms: Vec<(f64, [u32; 3])>,
+    pub mat: Arc<dyn Material>,
+    pub bbox: AABB,
+}
+
+impl QuarticSurface {
+    pub fn new(terms: Vec<(f64, [u32; 3])>, bbox: AABB, mat: Arc<dyn Material>) -> Option<Self> {
+        if let Some((_, e)) = terms.iter().find(|(_, e)| e[0] + e[1] + e[2] > 4) {
+            eprintln!(
+                "ERROR: QuarticSurface term x^{} y^{} z^{} has a degree above 4.",
+                e[0], e[1], e[2]
+            );
+            return None;
+        }
+        Some(Self { terms, mat, bbox })
+    }
+    pub fn value(&self, p: Vector) -> f64 {
+        self.terms
+            .iter()
+            .map(|&(coefficient, e)| {
+                coefficient * p.x.powi(e[0] as i32) * p.y.powi(e[1] as i32) * p.z.powi(e[2] as i32)
+            })
+            .sum()
+    }
+    pub fn gradient(&self, p: Vector) -> Vector {
+        let partial = |coefficient: f64, e: [u32; 3], axis: usize| {
+            if e[axis] == 0 {
+                return 0.0;
+            }
+            let mut e = e;
+            let factor = e[axis] as f64;
+            e[axis] -= 1;
+            coefficient
+                * factor
+                * p.x.powi(e[0] as i32)
+                * p.y.powi(e[1] as i32)
+                * p.z.powi(e[2] as i32)
+        };
+        let mut g = Vector::default();
+        for &(coefficient, e) in self.terms.iter() {
+            g = g + Vector::new(
+                partial(coefficient, e, 0),
+                partial(coefficient, e, 1),
+                partial(coefficient, e, 2),
+            );
+        }
+        g
+    }
+    // Coefficients of the surface function along o + t d, in powers of t.
+    fn along(&self, o: Vector, d: Vector) -> [f64; 5] {
+        let power = |o: f64, d: f64, n: u32| {
+            let mut p = [0.0; 5];
+            p[0] = 1.0;
+            for _ in 0..n {
+                p = multiply(&p, &[o, d, 0.0, 0.0, 0.0]);
+            }
+            p
+        };
+        let mut c = [0.0; 5];
+        for &(coefficient, e) in self.terms.iter() {
+            let term = multiply(
+                &multiply(&power(o.x, d.x, e[0]), &power(o.y, d.y, e[1])),
+                &power(o.z, d.z, e[2]),
+            );
+            for k in 0..5 {
+                c[k] += coefficient * term[k];
+            }
+        }
+        c
+    }
+}
+
+// Product of two polynomials whose degrees add up to at most four.
+fn multiply(a: &[f64; 5], b: &[f64; 5]) -> [f64; 5] {
+    let mut c = [0.0; 5];
+    for i in 0..5 {
+        for j in 0..5 - i {
+            c[i + j] += a[i] * b[j];
+        }
+    }
+    c
+}
+
+impl Hittable for QuarticSurface {
+    fn hit(&self, r: &Ray, ray_t: &Interval) -> Option<HitRecord> {
+        let inside = self.bbox.hit_interval(r, ray_t)?;
+        // Expanding around the box entry keeps the coefficients small.
+        let start = inside.min;
+        let c = self.along(r.at(start), r.direction);
+        let t = solve_quartic(c, &Interval::new(0.0, inside.max - start))
+            .into_iter()
+            .map(|s| start + s)
+            .find(|&t| ray_t.surrounds(t))?;
+        let mut rec = HitRecord::new(r.at(t), Vector::default(), t, false);
+        let gradient = self.gradient(rec.p);
+        let outward_normal = if gradient.near_zero() {
+            r.direction.unit() * -1.0
+        } else {
+            gradient.unit()
+        };
+        rec.set_face_normal(r, &outward_normal);
+        let center = Vector::new(
+            0.5 * (self.bbox.x.min + self.bbox.x.max),
+            0.5 * (self.bbox.y.min + self.bbox.y.max),
+            0.5 * (self.bbox.z.min + self.bbox.z.max),
+        );
+        (rec.u, rec.v) = Sphere::get_sphere_uv(&(rec.p - center).unit());
+        rec.mat = Some(self.mat.clone());
+        Some(rec)
+    }
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::rtweekend::INFINITY;
+
+    // Coefficients of the product of (t - root) over `roots`, in powers of t.
+    fn from_roots(roots: &[f64]) -> [f64; 5] {
+        let mut c = [0.0; 5];
+        c[0] = 1.0;
+        for &root in roots {
+            c = multiply(&c, &[-root, 1.0, 0.0, 0.0, 0.0]);
+        }
+        c
+    }
+
+    fn assert_roots(c: [f64; 5], expected: &[f64]) {
+        let roots = solve_quartic(c, &Interval::new(-INFINITY, INFINITY));
+        assert_eq!(roots.len(), expected.len(), "{:?}", roots);
+        for (root, want) in roots.iter().zip(expected) {
+            assert!((root - want).abs() < 1e-7, "{} != {}", root, want);
+        }
+    }
+
+    #[test]
+    fn simple_roots_are_found_in_order() {
+        assert_roots(from_roots(&[3.0, -2.0, 0.5, 7.0]), &[-2.0, 0.5, 3.0, 7.0]);
+        assert_roots([1.0, 0.0, 1.0, 0.0, 1.0], &[]);
+    }
+
+    #[test]
+    fn touching_roots_are_reported_once() {
+        assert_roots(from_roots(&[1.0, 1.0, 3.0, 4.0]), &[1.0, 3.0, 4.0]);
+        // (t^2 - 2)^2 only touches zero at irrational points, where the computed
+        // value is a rounding error away from it.
+        let s = 2.0f64.sqrt();
+        assert_roots([4.0, 0.0, -4.0, 0.0, 1.0], &[-s, s]);
+    }
+
+    #[test]
+    fn terms_above_degree_four_are_rejected() {
+        let mat: Arc<dyn Material> = Arc::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5)));
+        let bbox = AABB::point_new(&Vector::new(-1.0, -1.0, -1.0), &Vector::new(1.0, 1.0, 1.0));
+        let sphere = vec![
+            (1.0, [2, 0, 0]),
+            (1.0, [0, 2, 0]),
+            (1.0, [0, 0, 2]),
+            (-0.25, [0, 0, 0]),
+        ];
+        let surface = QuarticSurface::new(sphere, bbox, mat.clone()).unwrap();
+        let r = Ray::new(Vector::new(0.0, 0.0, 3.0), Vector::new(0.0, 0.0, -1.0), 0.0);
+        let rec = surface.hit(&r, &Interval::new(0.001, INFINITY)).unwrap();
+        assert!((rec.t - 2.5).abs() < 1e-9);
+        assert!(QuarticSurface::new(vec![(1.0, [2, 2, 1])], bbox, mat).is_none());
+    }
+}
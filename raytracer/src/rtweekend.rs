@@ -29,3 +29,49 @@ pub fn random_int_range(min: u32, max: u32) -> u32 {
 pub fn rgb_to_gray(pixel: &Rgb<u8>) -> f64 {
     pixel[0] as f64 * 0.299 + pixel[1] as f64 * 0.587 + pixel[2] as f64 * 0.114
 }
+
+// Real roots of a*t^2 + b*t + c in ascending order, computed without the
+// cancellation of the textbook formula. A zero `a` degrades to the linear root.
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    if a == 0.0 {
+        if b == 0.0 {
+            return None;
+        }
+        let t = -c / b;
+        return Some((t, t));
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    if q == 0.0 {
+        return Some((0.0, 0.0));
+    }
+    let (t0, t1) = (q / a, c / q);
+    Some((t0.min(t1), t0.max(t1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quadratic_roots_are_ascending_and_accurate() {
+        assert_eq!(solve_quadratic(1.0, -1.0, -6.0), Some((-2.0, 3.0)));
+        assert_eq!(solve_quadratic(-2.0, 2.0, 12.0), Some((-2.0, 3.0)));
+        assert_eq!(solve_quadratic(1.0, 0.0, 1.0), None);
+        assert_eq!(solve_quadratic(1.0, -4.0, 4.0), Some((2.0, 2.0)));
+        // The textbook formula loses the small root to cancellation here.
+        let (small, large) = solve_quadratic(1.0, -1e8, 1.0).unwrap();
+        assert!((small - 1e-8).abs() < 1e-20, "{}", small);
+        assert!((large - 1e8).abs() < 1e-6, "{}", large);
+    }
+
+    #[test]
+    fn quadratic_without_square_term_is_linear() {
+        assert_eq!(solve_quadratic(0.0, 2.0, -3.0), Some((1.5, 1.5)));
+        assert_eq!(solve_quadratic(0.0, 0.0, 1.0), None);
+        assert_eq!(solve_quadratic(2.0, 0.0, 0.0), Some((0.0, 0.0)));
+    }
+}
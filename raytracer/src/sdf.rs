@@ -0,0 +1,422 @@
+use crate::aabb::AABB;
+use crate::disk::Disk;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::sphere::Sphere;
+use crate::vec3::Vector;
+
+use std::sync::Arc;
+
+// A signed distance function: negative inside, and never more than the true
+// distance to the surface, so a sphere of that radius can be stepped safely.
+pub trait Sdf: Send + Sync {
+    fn distance(&self, p: Vector) -> f64;
+    // Must enclose every point where the distance is negative.
+    fn bounding_box(&self) -> AABB;
+}
+
+fn max_zero(v: Vector) -> Vector {
+    Vector::new(v.x.max(0.0), v.y.max(0.0), v.z.max(0.0))
+}
+
+pub struct SdfSphere {
+    pub center: Vector,
+    pub radius: f64,
+}
+
+impl SdfSphere {
+    pub fn new(center: Vector, radius: f64) -> Self {
+        Self { center, radius }
+    }
+}
+
+impl Sdf for SdfSphere {
+    fn distance(&self, p: Vector) -> f64 {
+        (p - self.center).length() - self.radius
+    }
+    fn bounding_box(&self) -> AABB {
+        let r = Vector::new(self.radius, self.radius, self.radius);
+        AABB::point_new(&(self.center - r), &(self.center + r))
+    }
+}
+
+// An axis-aligned box reaching `half_extents` from its center, with the edges
+// rounded off by `radius`.
+pub struct RoundedBox {
+    pub center: Vector,
+    pub half_extents: Vector,
+    pub radius: f64,
+}
+
+impl RoundedBox {
+    pub fn new(center: Vector, half_extents: Vector, radius: f64) -> Self {
+        Self {
+            center,
+            half_extents,
+            radius,
+        }
+    }
+}
+
+impl Sdf for RoundedBox {
+    fn distance(&self, p: Vector) -> f64 {
+        let p = p - self.center;
+        let r = Vector::new(self.radius, self.radius, self.radius);
+        let q = Vector::new(p.x.abs(), p.y.abs(), p.z.abs()) - self.half_extents + r;
+        max_zero(q).length() + q.x.max(q.y.max(q.z)).min(0.0) - self.radius
+    }
+    fn bounding_box(&self) -> AABB {
+        AABB::point_new(
+            &(self.center - self.half_extents),
+            &(self.center + self.half_extents),
+        )
+    }
+}
+
+// All points within `radius` of the segment from `a` to `b`.
+pub struct Capsule {
+    pub a: Vector,
+    pub b: Vector,
+    pub radius: f64,
+}
+
+impl Capsule {
+    pub fn new(a: Vector, b: Vector, radius: f64) -> Self {
+        Self { a, b, radius }
+    }
+}
+
+impl Sdf for Capsule {
+    fn distance(&self, p: Vector) -> f64 {
+        let pa = p - self.a;
+        let ba = self.b - self.a;
+        let h = (pa.dot(&ba) / ba.length_square()).clamp(0.0, 1.0);
+        (pa - ba * h).length() - self.radius
+    }
+    fn bounding_box(&self) -> AABB {
+        let r = Vector::new(self.radius, self.radius, self.radius);
+        AABB::box_new(
+            &AABB::point_new(&(self.a - r), &(self.a + r)),
+            &AABB::point_new(&(self.b - r), &(self.b + r)),
+        )
+    }
+}
+
+// Same parametrization as `Torus`: a ring of `major_radius` perpendicular to
+// `axis`, swept by a tube of `minor_radius`.
+pub struct SdfTorus {
+    pub center: Vector,
+    pub frame: Onb,
+    pub major_radius: f64,
+    pub minor_radius: f64,
+}
+
+impl SdfTorus {
+    pub fn new(center: Vector, axis: Vector, major_radius: f64, minor_radius: f64) -> Self {
+        let mut frame: Onb = Default::default();
+        frame.build_from_w(axis);
+        Self {
+            center,
+            frame,
+            major_radius,
+            minor_radius,
+        }
+    }
+}
+
+impl Sdf for SdfTorus {
+    fn distance(&self, p: Vector) -> f64 {
+        let p = self.frame.coords(p - self.center);
+        let ring = (p.x * p.x + p.y * p.y).sqrt() - self.major_radius;
+        (ring * ring + p.z * p.z).sqrt() - self.minor_radius
+    }
+    fn bounding_box(&self) -> AABB {
+        let ring = Disk::circle_bounding_box(self.center, self.frame.axis[2], self.major_radius);
+        AABB::new(
+            ring.x.expand(2.0 * self.minor_radius),
+            ring.y.expand(2.0 * self.minor_radius),
+            ring.z.expand(2.0 * self.minor_radius),
+        )
+    }
+}
+
+// Union blended over a seam of width `k` with a polynomial smooth minimum;
+// `k` of zero gives the plain union. The blend adds at most k / 4 of material.
+pub struct SmoothUnion {
+    pub a: Arc<dyn Sdf>,
+    pub b: Arc<dyn Sdf>,
+    pub k: f64,
+}
+
+impl SmoothUnion {
+    pub fn new(a: Arc<dyn Sdf>, b: Arc<dyn Sdf>, k: f64) -> Self {
+        Self { a, b, k }
+    }
+}
+
+impl Sdf for SmoothUnion {
+    fn distance(&self, p: Vector) -> f64 {
+        let (da, db) = (self.a.distance(p), self.b.distance(p));
+        if self.k <= 0.0 {
+            return da.min(db);
+        }
+        let h = (0.5 + 0.5 * (db - da) / self.k).clamp(0.0, 1.0);
+        db + (da - db) * h - self.k * h * (1.0 - h)
+    }
+    fn bounding_box(&self) -> AABB {
+        let bbox = AABB::box_new(&self.a.bounding_box(), &self.b.bounding_box());
+        let pad = 0.5 * self.k.max(0.0);
+        AABB::new(bbox.x.expand(pad), bbox.y.expand(pad), bbox.z.expand(pad))
+    }
+}
+
+// `a` with `b` carved out, the cut edge rounded over a width of `k`.
+pub struct SmoothSubtraction {
+    pub a: Arc<dyn Sdf>,
+    pub b: Arc<dyn Sdf>,
+    pub k: f64,
+}
+
+impl SmoothSubtraction {
+    pub fn new(a: Arc<dyn Sdf>, b: Arc<dyn Sdf>, k: f64) -> Self {
+        Self { a, b, k }
+    }
+}
+
+impl Sdf for SmoothSubtraction {
+    fn distance(&self, p: Vector) -> f64 {
+        let (da, db) = (self.a.distance(p), self.b.distance(p));
+        if self.k <= 0.0 {
+            return da.max(-db);
+        }
+        let h = (0.5 - 0.5 * (da + db) / self.k).clamp(0.0, 1.0);
+        da + (-db - da) * h + self.k * h * (1.0 - h)
+    }
+    fn bounding_box(&self) -> AABB {
+        self.a.bounding_box()
+    }
+}
+
+// Copies of `shape` every `spacing` along each axis, `count` of them on either
+// side of the original. The distance is only exact while each copy stays
+// within its own cell.
+pub struct Repetition {
+    pub shape: Arc<dyn Sdf>,
+    pub spacing: Vector,
+    pub count: [u32; 3],
+}
+
+impl Repetition {
+    pub fn new(shape: Arc<dyn Sdf>, spacing: Vector, count: [u32; 3]) -> Self {
+        Self {
+            shape,
+            spacing,
+            count,
+        }
+    }
+}
+
+impl Sdf for Repetition {
+    fn distance(&self, p: Vector) -> f64 {
+        let cell = |x: f64, spacing: f64, count: u32| {
+            if count == 0 || spacing == 0.0 {
+                x
+            } else {
+                x - spacing * (x / spacing).round().clamp(-(count as f64), count as f64)
+            }
+        };
+        self.shape.distance(Vector::new(
+            cell(p.x, self.spacing.x, self.count[0]),
+            cell(p.y, self.spacing.y, self.count[1]),
+            cell(p.z, self.spacing.z, self.count[2]),
+        ))
+    }
+    fn bounding_box(&self) -> AABB {
+        let bbox = self.shape.bounding_box();
+        let reach = |spacing: f64, count: u32| 2.0 * spacing.abs() * count as f64;
+        AABB::new(
+            bbox.x.expand(reach(self.spacing.x, self.count[0])),
+            bbox.y.expand(reach(self.spacing.y, self.count[1])),
+            bbox.z.expand(reach(self.spacing.z, self.count[2])),
+        )
+    }
+}
+
+// Renders the zero level set of an `Sdf` by sphere tracing inside its bounding
+// box. Rays starting inside march outwards to the exit, so refractive
+// materials work too. UVs are spherical coordinates around the box center.
+pub struct SdfObject {
+    pub sdf: Arc<dyn Sdf>,
+    pub mat: Arc<dyn Material>,
+    pub epsilon: f64,
+    pub max_steps: u32,
+    pub bbox: AABB,
+}
+
+impl SdfObject {
+    pub fn new(sdf: Arc<dyn Sdf>, mat: Arc<dyn Material>) -> Self {
+        let bbox = sdf.bounding_box();
+        Self {
+            sdf,
+            mat,
+            epsilon: 1e-4,
+            max_steps: 512,
+            bbox,
+        }
+    }
+    pub fn normal(&self, p: Vector) -> Vector {
+        // Tetrahedral central differences need four evaluations instead of six.
+        let h = self.epsilon;
+        let k = [
+            Vector::new(1.0, -1.0, -1.0),
+            Vector::new(-1.0, -1.0, 1.0),
+            Vector::new(-1.0, 1.0, -1.0),
+            Vector::new(1.0, 1.0, 1.0),
+        ];
+        let mut gradient = Vector::default();
+        for k in k.iter() {
+            gradient = gradient + *k * self.sdf.distance(p + *k * h);
+        }
+        gradient.unit()
+    }
+}
+
+impl Hittable for SdfObject {
+    fn hit(&self, r: &Ray, ray_t: &Interval) -> Option<HitRecord> {
+        let inside = self.bbox.hit_interval(r, ray_t)?;
+        let length = r.direction.length();
+        let mut t = inside.min;
+        let start = self.sdf.distance(r.at(t));
+        // Which side the ray is on; when starting on the surface itself, as
+        // scattered rays do, it is the side the ray is heading to.
+        let side = if start.abs() >= self.epsilon {
+            start.signum()
+        } else if self.normal(r.at(t)).dot(&r.direction) >= 0.0 {
+            1.0
+        } else {
+            -1.0
+        };
+        let mut left_surface = start * side >= self.epsilon;
+        for _ in 0..self.max_steps {
+            let distance = side * self.sdf.distance(r.at(t));
+            // A ray that never left the band around its starting surface only
+            // counts once it is clearly through to the other side.
+            if distance < self.epsilon && (left_surface || distance <= -self.epsilon) {
+                let mut rec = HitRecord::new(r.at(t), Vector::default(), t, false);
+                rec.set_face_normal(r, &self.normal(rec.p));
+                let center = Vector::new(
+                    0.5 * (self.bbox.x.min + self.bbox.x.max),
+                    0.5 * (self.bbox.y.min + self.bbox.y.max),
+                    0.5 * (self.bbox.z.min + self.bbox.z.max),
+                );
+                (rec.u, rec.v) = Sphere::get_sphere_uv(&(rec.p - center).unit());
+                rec.mat = Some(self.mat.clone());
+                return Some(rec);
+            }
+            // Surfaces may touch the box, so its far side is tested before giving up.
+            if t >= inside.max {
+                return None;
+            }
+            left_surface |= distance >= self.epsilon;
+            t = (t + distance.max(self.epsilon) / length).min(inside.max);
+        }
+        None
+    }
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::rtweekend::INFINITY;
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5)))
+    }
+
+    // Every point of a lattice around the box that lies outside it must be
+    // outside the shape as well.
+    fn assert_box_contains_shape(sdf: &dyn Sdf) {
+        let bbox = sdf.bounding_box();
+        let n = 40;
+        let lerp = |axis: &Interval, i: usize| {
+            axis.min - 1.0 + (axis.max - axis.min + 2.0) * i as f64 / (n - 1) as f64
+        };
+        let mut outside = 0;
+        for i in 0..n {
+            for j in 0..n {
+                for k in 0..n {
+                    let p = Vector::new(lerp(&bbox.x, i), lerp(&bbox.y, j), lerp(&bbox.z, k));
+                    if bbox.x.contains(p.x) && bbox.y.contains(p.y) && bbox.z.contains(p.z) {
+                        continue;
+                    }
+                    outside += 1;
+                    assert!(sdf.distance(p) >= -1e-12, "{:?} is inside", (p.x, p.y, p.z));
+                }
+            }
+        }
+        assert!(outside > 0);
+    }
+
+    #[test]
+    fn sphere_is_hit_where_the_analytic_sphere_is() {
+        let center = Vector::new(0.5, -1.0, 2.0);
+        let traced = SdfObject::new(Arc::new(SdfSphere::new(center, 1.5)), material());
+        let exact = Sphere::new(center, 1.5, material());
+        let ray_t = Interval::new(0.001, INFINITY);
+        let mut hits = 0;
+        for n in 0..400 {
+            let origin = Vector::new(
+                -4.0 + (n % 20) as f64 * 0.45,
+                3.0,
+                -3.0 + (n / 20) as f64 * 0.5,
+            );
+            let r = Ray::new(origin, Vector::new(0.2, -1.0, 0.3), 0.0);
+            match (traced.hit(&r, &ray_t), exact.hit(&r, &ray_t)) {
+                (None, None) => {}
+                (Some(a), Some(b)) => {
+                    assert!((a.t - b.t).abs() < 1e-3, "ray {}: {} != {}", n, a.t, b.t);
+                    assert!((a.normal - b.normal).length() < 1e-3, "ray {}", n);
+                    hits += 1;
+                }
+                // Rays grazing the silhouette may fall either way.
+                (a, b) => {
+                    let grazing = a.or(b).unwrap();
+                    let cosine = grazing.normal.dot(&r.direction.unit()).abs();
+                    assert!(cosine < 0.05, "ray {} hit only one sphere", n);
+                }
+            }
+        }
+        assert!(hits > 20, "{}", hits);
+        // From inside the surface is left at the far side.
+        let r = Ray::new(center, Vector::new(0.0, 0.0, 2.0), 0.0);
+        let rec = traced.hit(&r, &ray_t).unwrap();
+        assert!((rec.t - 0.75).abs() < 1e-3);
+        assert!(!rec.front_face);
+    }
+
+    #[test]
+    fn bounding_boxes_contain_the_shapes() {
+        assert_box_contains_shape(&RoundedBox::new(
+            Vector::new(1.0, 0.0, -1.0),
+            Vector::new(1.0, 0.5, 2.0),
+            0.3,
+        ));
+        assert_box_contains_shape(&Capsule::new(
+            Vector::new(-1.0, 0.5, 0.0),
+            Vector::new(2.0, -1.0, 1.0),
+            0.4,
+        ));
+        assert_box_contains_shape(&Repetition::new(
+            Arc::new(SdfSphere::new(Vector::new(0.2, 0.0, -0.1), 0.5)),
+            Vector::new(1.5, 0.0, 2.0),
+            [2, 0, 1],
+        ));
+    }
+}
@@ -100,6 +100,15 @@ impl Hittable for Sphere {
             let outward_normal: Vector = (rec.p - self.center1) / self.radius;
             rec.set_face_normal(r, &outward_normal);
             (rec.u, rec.v) = Self::get_sphere_uv(&outward_normal);
+            // Differentiating the mapping of `get_sphere_uv`, with phi = 2 pi u.
+            let (sin_phi, cos_phi) = (2.0 * PI * rec.u).sin_cos();
+            let n = outward_normal;
+            rec.dpdu = Vector::new(n.z, 0.0, -n.x) * (2.0 * PI * self.radius);
+            rec.dpdv = Vector::new(
+                cos_phi * n.y,
+                (1.0 - n.y * n.y).max(0.0).sqrt(),
+                -sin_phi * n.y,
+            ) * (PI * self.radius);
             rec.mat = self.mat.clone();
 
             Some(rec)
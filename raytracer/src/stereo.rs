@@ -0,0 +1,132 @@
+use image::{ImageBuffer, RgbImage};
+use std::sync::Arc;
+
+use crate::camera::Camera;
+use crate::hittable::Hittable;
+use crate::hittable_list::HittableList;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum StereoLayout {
+    SideBySide,
+    OverUnder,
+    Anaglyph,
+}
+
+pub struct StereoCamera {
+    pub camera: Camera,
+    pub interocular: f64,
+    pub convergence: f64,
+    pub layout: StereoLayout,
+}
+
+impl StereoCamera {
+    // None, after reporting it, if the eyes are not set to converge at a positive
+    // finite distance.
+    pub fn new(
+        camera: Camera,
+        interocular: f64,
+        convergence: f64,
+        layout: StereoLayout,
+    ) -> Option<Self> {
+        if !(convergence > 0.0 && convergence.is_finite()) {
+            eprintln!(
+                "ERROR: Stereo convergence must be a positive distance, got {}.",
+                convergence
+            );
+            return None;
+        }
+        Some(Self {
+            camera,
+            interocular,
+            convergence,
+            layout,
+        })
+    }
+    // Both eyes look parallel along the shared `w` and shift their viewports
+    // (off-axis projection), so the two frusta meet exactly on the plane
+    // `convergence` units in front of the camera and there is no vertical parallax.
+    pub fn eye_camera(&self, right_eye: bool) -> Camera {
+        let mut center = self.camera.clone();
+        center.initialise();
+        let offset = if right_eye {
+            self.interocular / 2.0
+        } else {
+            -self.interocular / 2.0
+        };
+        let mut eye = self.camera.clone();
+        eye.lookfrom = center.lookfrom + center.u * offset;
+        eye.lookat = center.lookat + center.u * offset;
+        eye.lens_shift =
+            self.camera.lens_shift - offset * self.camera.focus_dist / self.convergence;
+        eye
+    }
+    pub fn render(&self, world: HittableList, lights: Arc<dyn Hittable>, filename: &str) {
+        let left = self.eye_camera(false).render_image(&world, lights.clone());
+        let right = self.eye_camera(true).render_image(&world, lights);
+        Camera::save_image(self.compose(&left, &right), filename);
+    }
+    pub fn compose(&self, left: &RgbImage, right: &RgbImage) -> RgbImage {
+        let width = left.width();
+        let height = left.height();
+        match self.layout {
+            StereoLayout::SideBySide => ImageBuffer::from_fn(width * 2, height, |x, y| {
+                if x < width {
+                    *left.get_pixel(x, y)
+                } else {
+                    *right.get_pixel(x - width, y)
+                }
+            }),
+            StereoLayout::OverUnder => ImageBuffer::from_fn(width, height * 2, |x, y| {
+                if y < height {
+                    *left.get_pixel(x, y)
+                } else {
+                    *right.get_pixel(x, y - height)
+                }
+            }),
+            StereoLayout::Anaglyph => ImageBuffer::from_fn(width, height, |x, y| {
+                let l = left.get_pixel(x, y);
+                let r = right.get_pixel(x, y);
+                image::Rgb([l[0], r[1], r[2]])
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec3::Vector;
+
+    #[test]
+    fn eyes_converge_at_the_requested_distance() {
+        let mut camera =
+            Camera::direction_new(Vector::new(1.0, 2.0, 3.0), Vector::new(1.0, 0.0, -1.0));
+        camera.image_width = 101;
+        camera.focus_dist = 4.0;
+        let stereo =
+            StereoCamera::new(camera.clone(), 0.065, 2.5, StereoLayout::SideBySide).unwrap();
+        let target = camera.lookfrom + (camera.lookat - camera.lookfrom).unit() * 2.5;
+        for right_eye in [false, true] {
+            let mut eye = stereo.eye_camera(right_eye);
+            eye.initialise();
+            // The middle pixel of the odd-sized image looks along the shifted axis.
+            let middle = eye.pixel00_loc
+                + eye.pixel_delta_u * ((eye.image_width / 2) as f64)
+                + eye.pixel_delta_v * ((eye.image_height / 2) as f64);
+            let direction = middle - eye.center;
+            let t = 2.5 / direction.dot(&(eye.w * -1.0));
+            let point = eye.center + direction * t;
+            assert!((point - target).length() < 1e-9, "right eye {}", right_eye);
+        }
+    }
+
+    #[test]
+    fn convergence_must_be_a_positive_distance() {
+        for convergence in [0.0, -1.0, f64::INFINITY, f64::NAN] {
+            let camera = Camera::default();
+            assert!(
+                StereoCamera::new(camera, 0.065, convergence, StereoLayout::Anaglyph).is_none()
+            );
+        }
+    }
+}
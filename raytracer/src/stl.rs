@@ -0,0 +1,422 @@
+use crate::material::Material;
+use crate::mesh::TriangleMesh;
+use crate::vec3::Vector;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub enum StlError {
+    Io(std::io::Error),
+    InvalidData(String),
+}
+
+impl fmt::Display for StlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StlError::Io(e) => write!(f, "failed to read STL file: {}", e),
+            StlError::InvalidData(msg) => write!(f, "invalid STL data: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for StlError {}
+
+impl From<std::io::Error> for StlError {
+    fn from(e: std::io::Error) -> Self {
+        StlError::Io(e)
+    }
+}
+
+// STL stores three unshared vertices per facet. `weld_tolerance` merges vertices
+// closer than that distance (`Some(0.0)` merges exact duplicates only). By
+// default the stored facet normal decides which way a facet faces and shading
+// stays flat; `recompute_normals` ignores the stored normals, which CAD tools
+// often leave zero or stale, trusts the counter-clockwise winding instead and
+// smooths across edges whose faces meet within `crease_angle` degrees while
+// keeping sharper edges hard; without a `weld_tolerance` it welds exact
+// duplicates, since smoothing needs shared vertices.
+#[derive(Copy, Clone)]
+pub struct StlImportOptions {
+    pub weld_tolerance: Option<f64>,
+    pub recompute_normals: bool,
+    pub crease_angle: f64,
+}
+
+impl Default for StlImportOptions {
+    fn default() -> Self {
+        Self {
+            weld_tolerance: None,
+            recompute_normals: false,
+            crease_angle: 30.0,
+        }
+    }
+}
+
+struct Facet {
+    normal: Vector,
+    vertices: [Vector; 3],
+}
+
+pub fn load_stl<P: AsRef<Path>>(
+    path: P,
+    mat: Arc<dyn Material>,
+    options: &StlImportOptions,
+) -> Result<TriangleMesh, StlError> {
+    parse_stl(&std::fs::read(path)?, mat, options)
+}
+
+fn parse_stl(
+    data: &[u8],
+    mat: Arc<dyn Material>,
+    options: &StlImportOptions,
+) -> Result<TriangleMesh, StlError> {
+    // ASCII files start with "solid", but so do many binary headers, so the
+    // size implied by the binary facet count decides.
+    let binary_size = data
+        .get(80..84)
+        .map(|b| 84 + 50 * u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize);
+    let facets = if binary_size == Some(data.len()) {
+        parse_binary(data)
+    } else if data.starts_with(b"solid") {
+        parse_ascii(&String::from_utf8_lossy(data))?
+    } else {
+        return Err(StlError::InvalidData(
+            "neither ASCII nor a binary file of the expected size".to_string(),
+        ));
+    };
+
+    let mut positions = Vec::new();
+    let mut indices = Vec::new();
+    let weld_tolerance = match (options.weld_tolerance, options.recompute_normals) {
+        (None, true) => Some(0.0),
+        (tolerance, _) => tolerance,
+    };
+    let mut welder = Welder::new(weld_tolerance);
+    for facet in facets.iter() {
+        let [a, b, c] = facet.vertices;
+        let winding = (b - a).cross(&(c - a));
+        let corners = if !options.recompute_normals && winding.dot(&facet.normal) < 0.0 {
+            [a, c, b]
+        } else {
+            [a, b, c]
+        };
+        let face = corners.map(|p| welder.index(p, &mut positions));
+        if face[0] != face[1] && face[1] != face[2] && face[0] != face[2] {
+            indices.push(face);
+        }
+    }
+    if !options.recompute_normals {
+        return Ok(TriangleMesh::new(
+            positions,
+            Vec::new(),
+            Vec::new(),
+            indices,
+            mat,
+        ));
+    }
+
+    let face_normals: Vec<Vector> = indices
+        .iter()
+        .map(|f| {
+            let [a, b, c] = f.map(|i| positions[i as usize]);
+            (b - a).cross(&(c - a))
+        })
+        .collect();
+    let mut incident: Vec<Vec<usize>> = vec![Vec::new(); positions.len()];
+    for (k, face) in indices.iter().enumerate() {
+        for &i in face {
+            incident[i as usize].push(k);
+        }
+    }
+    // Each corner averages the area-weighted normals of the faces around its
+    // vertex that lie within the crease angle of its own face, and corners that
+    // end up with the same normal share a vertex again.
+    let cos_crease = crease_cos(options.crease_angle);
+    let mut split_positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut split: HashMap<(u32, [u64; 3]), u32> = HashMap::new();
+    let mut split_indices = Vec::with_capacity(indices.len());
+    for (k, face) in indices.iter().enumerate() {
+        let own = face_normals[k].unit();
+        let corners = face.map(|i| {
+            let mut sum = Vector::default();
+            for &other in incident[i as usize].iter() {
+                if face_normals[other].unit().dot(&own) >= cos_crease {
+                    sum = sum + face_normals[other];
+                }
+            }
+            let n = if sum.near_zero() { own } else { sum.unit() };
+            let key = (i, [n.x.to_bits(), n.y.to_bits(), n.z.to_bits()]);
+            *split.entry(key).or_insert_with(|| {
+                split_positions.push(positions[i as usize]);
+                normals.push(n);
+                (split_positions.len() - 1) as u32
+            })
+        });
+        split_indices.push(corners);
+    }
+    Ok(TriangleMesh::new(
+        split_positions,
+        normals,
+        Vec::new(),
+        split_indices,
+        mat,
+    ))
+}
+
+fn crease_cos(angle: f64) -> f64 {
+    angle.clamp(0.0, 180.0).to_radians().cos()
+}
+
+fn parse_binary(data: &[u8]) -> Vec<Facet> {
+    data[84..]
+        .chunks_exact(50)
+        .map(|chunk| {
+            let read = |offset: usize| {
+                let b = &chunk[offset..offset + 4];
+                f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64
+            };
+            let vector =
+                |offset: usize| Vector::new(read(offset), read(offset + 4), read(offset + 8));
+            Facet {
+                normal: vector(0),
+                vertices: [vector(12), vector(24), vector(36)],
+            }
+        })
+        .collect()
+}
+
+fn parse_ascii(text: &str) -> Result<Vec<Facet>, StlError> {
+    let mut facets = Vec::new();
+    let mut tokens = text.split_whitespace();
+    let mut normal = Vector::default();
+    let mut vertices = Vec::with_capacity(3);
+    let read_vector = |tokens: &mut std::str::SplitWhitespace| -> Result<Vector, StlError> {
+        let mut xyz = [0.0; 3];
+        for x in xyz.iter_mut() {
+            let token = tokens
+                .next()
+                .ok_or_else(|| StlError::InvalidData("unexpected end of file".to_string()))?;
+            *x = token
+                .parse()
+                .map_err(|_| StlError::InvalidData(format!("'{}' is not a number", token)))?;
+        }
+        Ok(Vector::new(xyz[0], xyz[1], xyz[2]))
+    };
+    while let Some(token) = tokens.next() {
+        match token {
+            "facet" => {
+                vertices.clear();
+                normal = Vector::default();
+            }
+            "normal" => normal = read_vector(&mut tokens)?,
+            "vertex" => vertices.push(read_vector(&mut tokens)?),
+            "endfacet" => {
+                if vertices.len() != 3 {
+                    return Err(StlError::InvalidData(format!(
+                        "facet with {} vertices",
+                        vertices.len()
+                    )));
+                }
+                facets.push(Facet {
+                    normal,
+                    vertices: [vertices[0], vertices[1], vertices[2]],
+                });
+            }
+            _ => {}
+        }
+    }
+    Ok(facets)
+}
+
+// Vertices are bucketed on a grid of `tolerance`-sized cells, so a match can only
+// be in the same or a neighbouring cell.
+struct Welder {
+    tolerance: Option<f64>,
+    cells: HashMap<[i64; 3], Vec<u32>>,
+}
+
+impl Welder {
+    fn new(tolerance: Option<f64>) -> Self {
+        Self {
+            tolerance,
+            cells: HashMap::new(),
+        }
+    }
+    fn index(&mut self, p: Vector, positions: &mut Vec<Vector>) -> u32 {
+        let tolerance = match self.tolerance {
+            Some(tolerance) => tolerance,
+            None => {
+                positions.push(p);
+                return (positions.len() - 1) as u32;
+            }
+        };
+        // Adding zero turns -0.0 into 0.0, which would otherwise get its own bits.
+        // Far out the cells saturate at the ends of i64, so neighbours wrap.
+        let cell = |x: f64| {
+            if tolerance > 0.0 {
+                (x / tolerance).floor() as i64
+            } else {
+                (x + 0.0).to_bits() as i64
+            }
+        };
+        let key = [cell(p.x), cell(p.y), cell(p.z)];
+        let reach = if tolerance > 0.0 { 1 } else { 0 };
+        for dx in -reach..=reach {
+            for dy in -reach..=reach {
+                for dz in -reach..=reach {
+                    let neighbour = [
+                        key[0].wrapping_add(dx),
+                        key[1].wrapping_add(dy),
+                        key[2].wrapping_add(dz),
+                    ];
+                    if let Some(candidates) = self.cells.get(&neighbour) {
+                        for &i in candidates {
+                            if (positions[i as usize] - p).length() <= tolerance {
+                                return i;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        positions.push(p);
+        let index = (positions.len() - 1) as u32;
+        self.cells.entry(key).or_default().push(index);
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5)))
+    }
+
+    // A unit square in z = 0, wound counter-clockwise around +z but with facet
+    // normals claiming -z.
+    const SQUARE: &str = "solid square
+facet normal 0 0 -1
+ outer loop
+  vertex 0 0 0
+  vertex 1 0 0
+  vertex 1 1 0
+ endloop
+endfacet
+facet normal 0 0 -1
+ outer loop
+  vertex 0 0 0
+  vertex 1 1 0
+  vertex 0 1 0
+ endloop
+endfacet
+endsolid square
+";
+
+    // Two facets folded at a right angle along the edge from the origin to +y,
+    // behind a header that starts with "solid" like many exporters write.
+    fn fold() -> Vec<u8> {
+        let mut data = b"solid binary".to_vec();
+        data.resize(80, 0);
+        data.extend_from_slice(&2u32.to_le_bytes());
+        let facets = [
+            [
+                [0.0f32, 0.0, 1.0],
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0],
+            ],
+            [
+                [1.0, 0.0, 0.0],
+                [0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0],
+                [0.0, 0.0, 1.0],
+            ],
+        ];
+        for facet in facets.iter() {
+            for x in facet.iter().flatten() {
+                data.extend_from_slice(&x.to_le_bytes());
+            }
+            data.extend_from_slice(&[0, 0]);
+        }
+        data
+    }
+
+    fn face_normal(mesh: &TriangleMesh, face: usize) -> Vector {
+        let [a, b, c] = mesh.indices[face].map(|i| mesh.positions[i as usize]);
+        (b - a).cross(&(c - a)).unit()
+    }
+
+    #[test]
+    fn ascii_follows_stored_normals_by_default() {
+        let mesh = parse_stl(SQUARE.as_bytes(), material(), &Default::default()).unwrap();
+        assert_eq!(mesh.positions.len(), 6);
+        assert!(mesh.normals.is_empty());
+        for face in 0..2 {
+            assert!((face_normal(&mesh, face).z + 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn recomputing_normals_welds_and_trusts_the_winding() {
+        let options = StlImportOptions {
+            recompute_normals: true,
+            ..Default::default()
+        };
+        let mesh = parse_stl(SQUARE.as_bytes(), material(), &options).unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.normals.len(), 4);
+        for n in mesh.normals.iter() {
+            assert!((n.z - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn binary_keeps_creases_sharp_and_smooths_below_the_angle() {
+        let sharp = StlImportOptions {
+            recompute_normals: true,
+            ..Default::default()
+        };
+        let mesh = parse_stl(&fold(), material(), &sharp).unwrap();
+        assert_eq!(mesh.indices.len(), 2);
+        // The two vertices on the fold are split, one copy per side.
+        assert_eq!(mesh.positions.len(), 6);
+        let smooth = StlImportOptions {
+            crease_angle: 120.0,
+            ..sharp
+        };
+        let mesh = parse_stl(&fold(), material(), &smooth).unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        let diagonal = Vector::new(1.0, 0.0, 1.0).unit();
+        assert!((mesh.normals[0] - diagonal).near_zero());
+    }
+
+    #[test]
+    fn welding_matches_signed_zeros_and_survives_tiny_tolerances() {
+        let mut positions = Vec::new();
+        let mut exact = Welder::new(Some(0.0));
+        let a = exact.index(Vector::new(0.0, 1.0, -0.0), &mut positions);
+        let b = exact.index(Vector::new(-0.0, 1.0, 0.0), &mut positions);
+        assert_eq!(a, b);
+        assert_eq!(positions.len(), 1);
+        let mut tiny = Welder::new(Some(1e-300));
+        let far = Vector::new(1e10, -1e10, 0.5);
+        let a = tiny.index(far, &mut positions);
+        let b = tiny.index(far, &mut positions);
+        assert_eq!(a, b);
+        assert_eq!(positions.len(), 2);
+    }
+
+    #[test]
+    fn unknown_data_is_rejected() {
+        assert!(matches!(
+            parse_stl(b"not an stl file", material(), &Default::default()),
+            Err(StlError::InvalidData(_))
+        ));
+    }
+}
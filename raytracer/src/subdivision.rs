@@ -0,0 +1,634 @@
+use crate::material::Material;
+use crate::mesh::TriangleMesh;
+use crate::rtweekend::PI;
+use crate::vec3::Vector;
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SubdivisionScheme {
+    // Triangles split in four; polygons are fanned into triangles first.
+    Loop,
+    // Any polygon split into quads, one per corner.
+    CatmullClark,
+}
+
+// Boundaries always stay sharp. With a `crease_angle`, in degrees, so do the
+// edges whose faces meet at a larger angle than that.
+#[derive(Copy, Clone)]
+pub struct Subdivision {
+    pub scheme: SubdivisionScheme,
+    pub levels: u32,
+    pub crease_angle: Option<f64>,
+}
+
+// A polygon mesh that keeps the connectivity subdivision needs. `uv_faces` is
+// either empty or holds, for every face, the indices into `uvs` of its corners,
+// so UV seams don't cut the surface apart. UVs are interpolated linearly, which
+// keeps textures exactly where they were on the control mesh. `creases` holds
+// sharp edges as sorted pairs of position indices.
+#[derive(Clone, Default)]
+pub struct PolygonMesh {
+    pub positions: Vec<Vector>,
+    pub faces: Vec<Vec<u32>>,
+    pub uvs: Vec<(f64, f64)>,
+    pub uv_faces: Vec<Vec<u32>>,
+    pub creases: HashSet<(u32, u32)>,
+}
+
+fn edge_key(a: u32, b: u32) -> (u32, u32) {
+    if a < b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+// Every edge once, in the order the faces meet them, with the faces on it.
+struct Edges {
+    index: HashMap<(u32, u32), usize>,
+    keys: Vec<(u32, u32)>,
+    faces: Vec<Vec<usize>>,
+}
+
+impl Edges {
+    fn new(faces: &[Vec<u32>]) -> Self {
+        let mut val = Self {
+            index: HashMap::new(),
+            keys: Vec::new(),
+            faces: Vec::new(),
+        };
+        for (f, face) in faces.iter().enumerate() {
+            for i in 0..face.len() {
+                let key = edge_key(face[i], face[(i + 1) % face.len()]);
+                let e = match val.index.get(&key) {
+                    Some(&e) => e,
+                    None => {
+                        val.index.insert(key, val.keys.len());
+                        val.keys.push(key);
+                        val.faces.push(Vec::new());
+                        val.keys.len() - 1
+                    }
+                };
+                val.faces[e].push(f);
+            }
+        }
+        val
+    }
+    fn at(&self, a: u32, b: u32) -> usize {
+        self.index[&edge_key(a, b)]
+    }
+}
+
+// Midpoints of the UV edges, shared between the faces on either side unless a
+// seam gives them different UV indices.
+struct UvMidpoints {
+    index: HashMap<(u32, u32), u32>,
+}
+
+impl UvMidpoints {
+    fn at(&mut self, a: u32, b: u32, uvs: &mut Vec<(f64, f64)>) -> u32 {
+        *self.index.entry(edge_key(a, b)).or_insert_with(|| {
+            let (ua, ub) = (uvs[a as usize], uvs[b as usize]);
+            uvs.push((0.5 * (ua.0 + ub.0), 0.5 * (ua.1 + ub.1)));
+            (uvs.len() - 1) as u32
+        })
+    }
+}
+
+impl PolygonMesh {
+    // Exported meshes often hold degenerate faces. A corner repeating the one
+    // before it is dropped, so a quad with a collapsed edge becomes a triangle,
+    // and faces that still revisit a vertex or have fewer than three corners are
+    // left out, as no edge can be shared by a face with itself.
+    pub fn new(
+        positions: Vec<Vector>,
+        faces: Vec<Vec<u32>>,
+        uvs: Vec<(f64, f64)>,
+        uv_faces: Vec<Vec<u32>>,
+    ) -> Self {
+        let has_uvs = !uv_faces.is_empty();
+        let mut kept_faces = Vec::with_capacity(faces.len());
+        let mut kept_uv_faces = Vec::with_capacity(uv_faces.len());
+        for (f, face) in faces.into_iter().enumerate() {
+            let m = face.len();
+            let corners: Vec<usize> = (0..m)
+                .filter(|&i| face[i] != face[(i + m - 1) % m])
+                .collect();
+            let distinct: HashSet<u32> = corners.iter().map(|&i| face[i]).collect();
+            if corners.len() < 3 || distinct.len() != corners.len() {
+                continue;
+            }
+            kept_faces.push(corners.iter().map(|&i| face[i]).collect());
+            if has_uvs {
+                kept_uv_faces.push(corners.iter().map(|&i| uv_faces[f][i]).collect());
+            }
+        }
+        Self {
+            positions,
+            faces: kept_faces,
+            uvs,
+            uv_faces: kept_uv_faces,
+            creases: HashSet::new(),
+        }
+    }
+    // Welds the vertices of a `TriangleMesh` that share a position, since
+    // loaders split them wherever normals or UVs differ.
+    pub fn from_triangle_mesh(mesh: &TriangleMesh) -> Self {
+        let mut positions = Vec::new();
+        let mut welded: HashMap<[u64; 3], u32> = HashMap::new();
+        let remap: Vec<u32> = mesh
+            .positions
+            .iter()
+            .map(|p| {
+                *welded
+                    .entry([p.x.to_bits(), p.y.to_bits(), p.z.to_bits()])
+                    .or_insert_with(|| {
+                        positions.push(*p);
+                        (positions.len() - 1) as u32
+                    })
+            })
+            .collect();
+        let faces = mesh
+            .indices
+            .iter()
+            .map(|f| f.iter().map(|&i| remap[i as usize]).collect())
+            .collect();
+        let uv_faces = if mesh.uvs.is_empty() {
+            Vec::new()
+        } else {
+            mesh.indices.iter().map(|f| f.to_vec()).collect()
+        };
+        Self::new(positions, faces, mesh.uvs.clone(), uv_faces)
+    }
+    // Area-weighted normal of a face, by Newell's method so that it also holds
+    // for polygons that are not quite planar.
+    fn face_vector(&self, f: usize) -> Vector {
+        let face = &self.faces[f];
+        let mut sum = Vector::default();
+        for i in 0..face.len() {
+            let a = self.positions[face[i] as usize];
+            let b = self.positions[face[(i + 1) % face.len()] as usize];
+            sum = sum + a.cross(&b);
+        }
+        sum * 0.5
+    }
+    pub fn mark_creases(&mut self, angle: f64) {
+        let cos_crease = angle.clamp(0.0, 180.0).to_radians().cos();
+        let edges = Edges::new(&self.faces);
+        for (e, key) in edges.keys.iter().enumerate() {
+            if let [f0, f1] = edges.faces[e][..] {
+                let (n0, n1) = (self.face_vector(f0), self.face_vector(f1));
+                if !n0.near_zero() && !n1.near_zero() && n0.unit().dot(&n1.unit()) < cos_crease {
+                    self.creases.insert(*key);
+                }
+            }
+        }
+    }
+    pub fn subdivide(&self, subdivision: &Subdivision) -> PolygonMesh {
+        let mut mesh = self.clone();
+        if let Some(angle) = subdivision.crease_angle {
+            mesh.mark_creases(angle);
+        }
+        for _ in 0..subdivision.levels {
+            mesh = match subdivision.scheme {
+                SubdivisionScheme::Loop => mesh.loop_step(),
+                SubdivisionScheme::CatmullClark => mesh.catmull_clark_step(),
+            };
+        }
+        mesh
+    }
+    fn is_sharp(&self, edges: &Edges, e: usize) -> bool {
+        edges.faces[e].len() != 2 || self.creases.contains(&edges.keys[e])
+    }
+    // The edges and faces around every vertex.
+    fn incidence(&self, edges: &Edges) -> (Vec<Vec<usize>>, Vec<Vec<usize>>) {
+        let mut vertex_edges = vec![Vec::new(); self.positions.len()];
+        for (e, &(a, b)) in edges.keys.iter().enumerate() {
+            vertex_edges[a as usize].push(e);
+            vertex_edges[b as usize].push(e);
+        }
+        let mut vertex_faces = vec![Vec::new(); self.positions.len()];
+        for (f, face) in self.faces.iter().enumerate() {
+            for &v in face.iter() {
+                vertex_faces[v as usize].push(f);
+            }
+        }
+        (vertex_edges, vertex_faces)
+    }
+    fn other(edges: &Edges, e: usize, v: u32) -> u32 {
+        let (a, b) = edges.keys[e];
+        if a == v {
+            b
+        } else {
+            a
+        }
+    }
+    // Both schemes share the rules for vertices on sharp edges: one between two
+    // of them moves along the crease curve, one where more meet is a corner and
+    // stays put. Others, including darts that end a single crease, are smooth.
+    fn sharp_vertex(&self, edges: &Edges, vertex_edges: &[usize], v: u32) -> Option<Vector> {
+        let sharp: Vec<usize> = vertex_edges
+            .iter()
+            .cloned()
+            .filter(|&e| self.is_sharp(edges, e))
+            .collect();
+        let p = self.positions[v as usize];
+        match sharp.len() {
+            0 | 1 if !vertex_edges.is_empty() => None,
+            2 => {
+                let a = self.positions[Self::other(edges, sharp[0], v) as usize];
+                let b = self.positions[Self::other(edges, sharp[1], v) as usize];
+                Some(p * 0.75 + (a + b) * 0.125)
+            }
+            _ => Some(p),
+        }
+    }
+    // Crease edges split along with the edges they lie on.
+    fn split_creases(&self, edges: &Edges, first_edge_point: u32) -> HashSet<(u32, u32)> {
+        let mut creases = HashSet::new();
+        for &(a, b) in self.creases.iter() {
+            if let Some(&e) = edges.index.get(&(a, b)) {
+                let middle = first_edge_point + e as u32;
+                creases.insert(edge_key(a, middle));
+                creases.insert(edge_key(middle, b));
+            }
+        }
+        creases
+    }
+    fn triangulated(&self) -> PolygonMesh {
+        let fan = |face: &Vec<u32>| -> Vec<Vec<u32>> {
+            (1..face.len().saturating_sub(1))
+                .map(|i| vec![face[0], face[i], face[i + 1]])
+                .collect()
+        };
+        PolygonMesh {
+            positions: self.positions.clone(),
+            faces: self.faces.iter().flat_map(fan).collect(),
+            uvs: self.uvs.clone(),
+            uv_faces: self.uv_faces.iter().flat_map(fan).collect(),
+            creases: self.creases.clone(),
+        }
+    }
+    fn loop_step(&self) -> PolygonMesh {
+        if self.faces.iter().any(|face| face.len() != 3) {
+            return self.triangulated().loop_step();
+        }
+        let edges = Edges::new(&self.faces);
+        let (vertex_edges, _) = self.incidence(&edges);
+        let n = self.positions.len() as u32;
+
+        let mut positions = Vec::with_capacity(self.positions.len() + edges.keys.len());
+        for v in 0..n {
+            let around = &vertex_edges[v as usize];
+            let p = self.positions[v as usize];
+            positions.push(self.sharp_vertex(&edges, around, v).unwrap_or_else(|| {
+                let k = around.len() as f64;
+                let c = 0.375 + 0.25 * (2.0 * PI / k).cos();
+                let beta = (0.625 - c * c) / k;
+                let mut sum = Vector::default();
+                for &e in around.iter() {
+                    sum = sum + self.positions[Self::other(&edges, e, v) as usize];
+                }
+                p * (1.0 - k * beta) + sum * beta
+            }));
+        }
+        for (e, &(a, b)) in edges.keys.iter().enumerate() {
+            let (pa, pb) = (self.positions[a as usize], self.positions[b as usize]);
+            if self.is_sharp(&edges, e) {
+                positions.push((pa + pb) * 0.5);
+                continue;
+            }
+            let mut opposite = Vector::default();
+            for &f in edges.faces[e].iter() {
+                let c = self.faces[f].iter().find(|&&v| v != a && v != b).unwrap();
+                opposite = opposite + self.positions[*c as usize];
+            }
+            positions.push((pa + pb) * 0.375 + opposite * 0.125);
+        }
+
+        let mut faces = Vec::with_capacity(4 * self.faces.len());
+        for face in self.faces.iter() {
+            let [a, b, c] = [face[0], face[1], face[2]];
+            let ab = n + edges.at(a, b) as u32;
+            let bc = n + edges.at(b, c) as u32;
+            let ca = n + edges.at(c, a) as u32;
+            faces.extend([
+                vec![a, ab, ca],
+                vec![ab, b, bc],
+                vec![ca, bc, c],
+                vec![ab, bc, ca],
+            ]);
+        }
+        let mut uvs = self.uvs.clone();
+        let mut uv_faces = Vec::with_capacity(4 * self.uv_faces.len());
+        let mut midpoints = UvMidpoints {
+            index: HashMap::new(),
+        };
+        for face in self.uv_faces.iter() {
+            let [a, b, c] = [face[0], face[1], face[2]];
+            let ab = midpoints.at(a, b, &mut uvs);
+            let bc = midpoints.at(b, c, &mut uvs);
+            let ca = midpoints.at(c, a, &mut uvs);
+            uv_faces.extend([
+                vec![a, ab, ca],
+                vec![ab, b, bc],
+                vec![ca, bc, c],
+                vec![ab, bc, ca],
+            ]);
+        }
+        PolygonMesh {
+            positions,
+            faces,
+            uvs,
+            uv_faces,
+            creases: self.split_creases(&edges, n),
+        }
+    }
+    fn catmull_clark_step(&self) -> PolygonMesh {
+        let edges = Edges::new(&self.faces);
+        let (vertex_edges, vertex_faces) = self.incidence(&edges);
+        let n = self.positions.len() as u32;
+        let first_face_point = n + edges.keys.len() as u32;
+
+        let face_points: Vec<Vector> = self
+            .faces
+            .iter()
+            .map(|face| {
+                let mut sum = Vector::default();
+                for &v in face.iter() {
+                    sum = sum + self.positions[v as usize];
+                }
+                sum / face.len() as f64
+            })
+            .collect();
+        let midpoint = |e: usize| {
+            let (a, b) = edges.keys[e];
+            (self.positions[a as usize] + self.positions[b as usize]) * 0.5
+        };
+
+        let mut positions =
+            Vec::with_capacity(self.positions.len() + edges.keys.len() + self.faces.len());
+        for v in 0..n {
+            let around = &vertex_edges[v as usize];
+            let p = self.positions[v as usize];
+            positions.push(self.sharp_vertex(&edges, around, v).unwrap_or_else(|| {
+                let k = around.len() as f64;
+                let mut q = Vector::default();
+                for &f in vertex_faces[v as usize].iter() {
+                    q = q + face_points[f];
+                }
+                q = q / vertex_faces[v as usize].len() as f64;
+                let mut r = Vector::default();
+                for &e in around.iter() {
+                    r = r + midpoint(e);
+                }
+                r = r / k;
+                (q + r * 2.0 + p * (k - 3.0)) / k
+            }));
+        }
+        for e in 0..edges.keys.len() {
+            if self.is_sharp(&edges, e) {
+                positions.push(midpoint(e));
+            } else {
+                let [f0, f1] = [edges.faces[e][0], edges.faces[e][1]];
+                positions.push((midpoint(e) * 2.0 + face_points[f0] + face_points[f1]) * 0.25);
+            }
+        }
+        positions.extend(face_points);
+
+        let mut faces = Vec::new();
+        for (f, face) in self.faces.iter().enumerate() {
+            let m = face.len();
+            for i in 0..m {
+                let (previous, v, next) = (face[(i + m - 1) % m], face[i], face[(i + 1) % m]);
+                faces.push(vec![
+                    v,
+                    n + edges.at(v, next) as u32,
+                    first_face_point + f as u32,
+                    n + edges.at(previous, v) as u32,
+                ]);
+            }
+        }
+        let mut uvs = self.uvs.clone();
+        let mut uv_faces = Vec::new();
+        let mut midpoints = UvMidpoints {
+            index: HashMap::new(),
+        };
+        for face in self.uv_faces.iter() {
+            let m = face.len();
+            let mut center = (0.0, 0.0);
+            for &t in face.iter() {
+                center = (
+                    center.0 + self.uvs[t as usize].0,
+                    center.1 + self.uvs[t as usize].1,
+                );
+            }
+            uvs.push((center.0 / m as f64, center.1 / m as f64));
+            let center = (uvs.len() - 1) as u32;
+            for i in 0..m {
+                let (previous, t, next) = (face[(i + m - 1) % m], face[i], face[(i + 1) % m]);
+                let after = midpoints.at(t, next, &mut uvs);
+                let before = midpoints.at(previous, t, &mut uvs);
+                uv_faces.push(vec![t, after, center, before]);
+            }
+        }
+        PolygonMesh {
+            positions,
+            faces,
+            uvs,
+            uv_faces,
+            creases: self.split_creases(&edges, n),
+        }
+    }
+    // Fans every polygon into triangles with smooth normals. The corners around a
+    // vertex share a normal unless a crease or boundary runs between them.
+    pub fn to_triangle_mesh(&self, mat: Arc<dyn Material>) -> TriangleMesh {
+        let edges = Edges::new(&self.faces);
+        let mut first_corner = Vec::with_capacity(self.faces.len());
+        let mut corners = 0;
+        for face in self.faces.iter() {
+            first_corner.push(corners);
+            corners += face.len();
+        }
+        let corner = |f: usize, v: u32| {
+            first_corner[f] + self.faces[f].iter().position(|&x| x == v).unwrap()
+        };
+        let mut parent: Vec<usize> = (0..corners).collect();
+        fn root(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+        for (e, &(a, b)) in edges.keys.iter().enumerate() {
+            if self.is_sharp(&edges, e) {
+                continue;
+            }
+            let [f0, f1] = [edges.faces[e][0], edges.faces[e][1]];
+            for v in [a, b] {
+                let (r0, r1) = (
+                    root(&mut parent, corner(f0, v)),
+                    root(&mut parent, corner(f1, v)),
+                );
+                parent[r0] = r1;
+            }
+        }
+        let mut sums = vec![Vector::default(); corners];
+        for (f, face) in self.faces.iter().enumerate() {
+            let area = self.face_vector(f);
+            for i in 0..face.len() {
+                let r = root(&mut parent, first_corner[f] + i);
+                sums[r] = sums[r] + area;
+            }
+        }
+
+        let has_uvs = !self.uv_faces.is_empty();
+        let mut mesh = TriangleMesh::new(Vec::new(), Vec::new(), Vec::new(), Vec::new(), mat);
+        let mut vertices: HashMap<(usize, u32), u32> = HashMap::new();
+        for (f, face) in self.faces.iter().enumerate() {
+            let ids: Vec<u32> = (0..face.len())
+                .map(|i| {
+                    let r = root(&mut parent, first_corner[f] + i);
+                    let uv = if has_uvs { self.uv_faces[f][i] } else { 0 };
+                    *vertices.entry((r, uv)).or_insert_with(|| {
+                        mesh.positions.push(self.positions[face[i] as usize]);
+                        let n = sums[r];
+                        mesh.normals.push(if n.near_zero() { n } else { n.unit() });
+                        if has_uvs {
+                            mesh.uvs.push(self.uvs[uv as usize]);
+                        }
+                        (mesh.positions.len() - 1) as u32
+                    })
+                })
+                .collect();
+            for i in 1..ids.len().saturating_sub(1) {
+                mesh.indices.push([ids[0], ids[i], ids[i + 1]]);
+            }
+        }
+        mesh
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: Vector, b: Vector) {
+        assert!((a - b).near_zero(), "({}, {}, {})", a.x, a.y, a.z);
+    }
+
+    fn tetrahedron() -> PolygonMesh {
+        PolygonMesh::new(
+            vec![
+                Vector::new(1.0, 1.0, 1.0),
+                Vector::new(1.0, -1.0, -1.0),
+                Vector::new(-1.0, 1.0, -1.0),
+                Vector::new(-1.0, -1.0, 1.0),
+            ],
+            vec![vec![0, 1, 2], vec![0, 3, 1], vec![0, 2, 3], vec![1, 3, 2]],
+            Vec::new(),
+            Vec::new(),
+        )
+    }
+
+    fn cube() -> PolygonMesh {
+        let mut positions = Vec::new();
+        for i in 0..8 {
+            let coordinate = |bit: u32| if i & (1 << bit) != 0 { 1.0 } else { -1.0 };
+            positions.push(Vector::new(coordinate(0), coordinate(1), coordinate(2)));
+        }
+        let faces = vec![
+            vec![0, 2, 3, 1],
+            vec![4, 5, 7, 6],
+            vec![0, 1, 5, 4],
+            vec![2, 6, 7, 3],
+            vec![0, 4, 6, 2],
+            vec![1, 3, 7, 5],
+        ];
+        PolygonMesh::new(positions, faces, Vec::new(), Vec::new())
+    }
+
+    fn subdivision(scheme: SubdivisionScheme, crease_angle: Option<f64>) -> Subdivision {
+        Subdivision {
+            scheme,
+            levels: 1,
+            crease_angle,
+        }
+    }
+
+    #[test]
+    fn loop_splits_triangles_in_four() {
+        let fine = tetrahedron().subdivide(&subdivision(SubdivisionScheme::Loop, None));
+        assert_eq!(fine.positions.len(), 4 + 6);
+        assert_eq!(fine.faces.len(), 16);
+        // Three neighbours give beta = 3 / 16, and they sum to -p.
+        let p = Vector::new(1.0, 1.0, 1.0);
+        assert_near(fine.positions[0], p * (1.0 - 9.0 / 16.0) - p * (3.0 / 16.0));
+        // An edge point weighs its ends by 3/8 and the opposite corners by 1/8.
+        let e = fine.faces[0][1] as usize;
+        let ends = p + Vector::new(1.0, -1.0, -1.0);
+        let opposite = Vector::new(-1.0, 1.0, -1.0) + Vector::new(-1.0, -1.0, 1.0);
+        assert_near(fine.positions[e], ends * 0.375 + opposite * 0.125);
+    }
+
+    #[test]
+    fn catmull_clark_splits_a_cube_into_quads() {
+        let fine = cube().subdivide(&subdivision(SubdivisionScheme::CatmullClark, None));
+        assert_eq!(fine.positions.len(), 8 + 12 + 6);
+        assert_eq!(fine.faces.len(), 24);
+        assert!(fine.faces.iter().all(|face| face.len() == 4));
+        assert_near(fine.positions[7], Vector::new(1.0, 1.0, 1.0) * (5.0 / 9.0));
+        assert_near(fine.positions[8 + 12], Vector::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn boundaries_and_creases_stay_sharp() {
+        let square = PolygonMesh::new(
+            vec![
+                Vector::new(0.0, 0.0, 0.0),
+                Vector::new(1.0, 0.0, 0.0),
+                Vector::new(1.0, 1.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+            ],
+            vec![vec![0, 1, 2, 3]],
+            Vec::new(),
+            Vec::new(),
+        );
+        let fine = square.subdivide(&subdivision(SubdivisionScheme::CatmullClark, None));
+        // A boundary vertex moves along the boundary curve, edge points are
+        // plain midpoints.
+        assert_near(fine.positions[0], Vector::new(0.125, 0.125, 0.0));
+        assert!(fine.positions[4..8]
+            .iter()
+            .any(|&p| (p - Vector::new(0.5, 0.0, 0.0)).near_zero()));
+
+        // Every cube edge is a 90 degree crease, so the corners stay put and the
+        // edges stay straight.
+        for scheme in [SubdivisionScheme::Loop, SubdivisionScheme::CatmullClark] {
+            let fine = cube().subdivide(&subdivision(scheme, Some(45.0)));
+            assert_near(fine.positions[7], Vector::new(1.0, 1.0, 1.0));
+            assert!(fine.creases.len() >= 24);
+            for &(a, b) in fine.creases.iter() {
+                let (pa, pb) = (fine.positions[a as usize], fine.positions[b as usize]);
+                let shared = [pa.x == pb.x, pa.y == pb.y, pa.z == pb.z];
+                assert_eq!(shared.iter().filter(|&&s| s).count(), 2);
+            }
+        }
+    }
+
+    #[test]
+    fn degenerate_faces_are_dropped_or_collapsed() {
+        let mut mesh = tetrahedron();
+        let mut faces = mesh.faces.clone();
+        faces.push(vec![0, 1, 0]);
+        faces[3] = vec![1, 3, 3, 2];
+        mesh = PolygonMesh::new(mesh.positions, faces, Vec::new(), Vec::new());
+        assert_eq!(mesh.faces.len(), 4);
+        assert_eq!(mesh.faces[3], vec![1, 3, 2]);
+        let fine = mesh.subdivide(&subdivision(SubdivisionScheme::Loop, None));
+        assert_eq!(fine.faces.len(), 16);
+    }
+}
@@ -0,0 +1,176 @@
+use crate::aabb::AABB;
+use crate::disk::Disk;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::quartic::solve_quartic;
+use crate::ray::Ray;
+use crate::rtweekend::{solve_quadratic, PI};
+use crate::vec3::Vector;
+
+use std::sync::Arc;
+
+// A torus around `center` whose ring of `major_radius` lies in the plane
+// perpendicular to `axis`, swept by a tube of `minor_radius`. u runs around the
+// axis and v around the tube, starting on its inner side.
+pub struct Torus {
+    pub center: Vector,
+    pub frame: Onb,
+    pub major_radius: f64,
+    pub minor_radius: f64,
+    pub mat: Arc<dyn Material>,
+    pub bbox: AABB,
+}
+
+impl Torus {
+    pub fn new(
+        center: Vector,
+        axis: Vector,
+        major_radius: f64,
+        minor_radius: f64,
+        mat: Arc<dyn Material>,
+    ) -> Self {
+        let mut frame: Onb = Default::default();
+        frame.build_from_w(axis);
+        let ring = Disk::circle_bounding_box(center, frame.axis[2], major_radius);
+        Self {
+            center,
+            frame,
+            major_radius,
+            minor_radius,
+            mat,
+            bbox: AABB::new(
+                ring.x.expand(2.0 * minor_radius),
+                ring.y.expand(2.0 * minor_radius),
+                ring.z.expand(2.0 * minor_radius),
+            ),
+        }
+    }
+}
+
+impl Hittable for Torus {
+    // Solves (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + y^2) in the local frame. The ray
+    // is first normalized and restarted where it enters the bounding sphere, so
+    // the quartic is solved over a short interval with well-scaled coefficients.
+    fn hit(&self, r: &Ray, ray_t: &Interval) -> Option<HitRecord> {
+        let big = self.major_radius;
+        let small = self.minor_radius;
+        let direction = self.frame.coords(r.direction);
+        let length = direction.length();
+        let d = direction / length;
+        let o = self.frame.coords(r.origin - self.center);
+        // The sphere touches the torus along its outer equator, so it is padded
+        // to keep roots there off the ends of the interval.
+        let outer = 1.001 * (big + small);
+        let (s0, s1) = solve_quadratic(1.0, 2.0 * o.dot(&d), o.length_square() - outer * outer)?;
+        let start = s0.max(ray_t.min * length);
+        let end = s1.min(ray_t.max * length);
+        if start >= end {
+            return None;
+        }
+        let o = o + d * start;
+
+        let e = o.dot(&d);
+        let f = o.length_square() + big * big - small * small;
+        let four_r2 = 4.0 * big * big;
+        let c = [
+            f * f - four_r2 * (o.x * o.x + o.y * o.y),
+            4.0 * e * f - 2.0 * four_r2 * (o.x * d.x + o.y * d.y),
+            4.0 * e * e + 2.0 * f - four_r2 * (d.x * d.x + d.y * d.y),
+            4.0 * e,
+            1.0,
+        ];
+        let t = solve_quartic(c, &Interval::new(0.0, end - start))
+            .into_iter()
+            .map(|s| (start + s) / length)
+            .find(|&t| ray_t.surrounds(t))?;
+
+        let p = self.frame.coords(r.at(t) - self.center);
+        let rho = (p.x * p.x + p.y * p.y).sqrt();
+        let ring = if rho > 0.0 {
+            Vector::new(p.x, p.y, 0.0) * (big / rho)
+        } else {
+            Vector::default()
+        };
+        let tube = p - ring;
+        let outward_normal = if tube.near_zero() {
+            self.frame.axis[2]
+        } else {
+            self.frame.vec_local(tube.unit())
+        };
+        let mut rec = HitRecord::new(r.at(t), Vector::default(), t, false);
+        rec.set_face_normal(r, &outward_normal);
+        rec.u = (p.y.atan2(p.x) + PI) / (2.0 * PI);
+        rec.v = (p.z.atan2(rho - big) + PI) / (2.0 * PI);
+        rec.dpdu = self.frame.local(-p.y, p.x, 0.0) * (2.0 * PI);
+        if rho > 0.0 {
+            rec.dpdv = self
+                .frame
+                .local(-p.z * p.x / rho, -p.z * p.y / rho, rho - big)
+                * (2.0 * PI);
+        }
+        rec.mat = Some(self.mat.clone());
+        Some(rec)
+    }
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::rtweekend::INFINITY;
+
+    // A ring of radius 2 around (1, -1, 0.5) in the xz-plane, with a tube of 0.5.
+    fn torus() -> Torus {
+        let mat: Arc<dyn Material> = Arc::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5)));
+        Torus::new(
+            Vector::new(1.0, -1.0, 0.5),
+            Vector::new(0.0, 1.0, 0.0),
+            2.0,
+            0.5,
+            mat,
+        )
+    }
+
+    #[test]
+    fn rays_along_the_axis_hit_the_top_of_the_tube() {
+        let r = Ray::new(Vector::new(3.0, 4.0, 0.5), Vector::new(0.0, -2.0, 0.0), 0.0);
+        let rec = torus().hit(&r, &Interval::new(0.001, INFINITY)).unwrap();
+        // The tube's top is at y = -0.5, 4.5 units down at half a unit per t.
+        assert!((rec.t - 2.25).abs() < 1e-9, "{}", rec.t);
+        assert!((rec.normal - Vector::new(0.0, 1.0, 0.0)).near_zero());
+        assert!(rec.front_face);
+    }
+
+    #[test]
+    fn rays_through_the_hole_miss() {
+        let r = Ray::new(Vector::new(1.0, 4.0, 0.5), Vector::new(0.0, -1.0, 0.0), 0.0);
+        assert!(torus().hit(&r, &Interval::new(0.001, INFINITY)).is_none());
+        // Slightly off the axis still passes through, as the hole is 1.5 wide.
+        let r = Ray::new(Vector::new(2.4, 4.0, 0.5), Vector::new(0.0, -1.0, 0.0), 0.0);
+        assert!(torus().hit(&r, &Interval::new(0.001, INFINITY)).is_none());
+    }
+
+    #[test]
+    fn normal_at_the_outer_equator_points_away_from_the_axis() {
+        let r = Ray::new(
+            Vector::new(1.0, -1.0, 10.0),
+            Vector::new(0.0, 0.0, -1.0),
+            0.0,
+        );
+        let rec = torus().hit(&r, &Interval::new(0.001, INFINITY)).unwrap();
+        assert!((rec.t - 7.0).abs() < 1e-9, "{}", rec.t);
+        assert!((rec.p - Vector::new(1.0, -1.0, 3.0)).near_zero());
+        assert!((rec.normal - Vector::new(0.0, 0.0, 1.0)).near_zero());
+        // From inside the tube the same point is left through its back face.
+        let r = Ray::new(Vector::new(1.0, -1.0, 2.5), Vector::new(0.0, 0.0, 1.0), 0.0);
+        let rec = torus().hit(&r, &Interval::new(0.001, INFINITY)).unwrap();
+        assert!((rec.t - 0.5).abs() < 1e-9, "{}", rec.t);
+        assert!(!rec.front_face);
+        assert!((rec.normal - Vector::new(0.0, 0.0, -1.0)).near_zero());
+    }
+}
@@ -1,3 +1,4 @@
+use std::cmp::Ordering;
 use std::sync::Arc;
 
 use crate::aabb::AABB;
@@ -10,81 +11,154 @@ use crate::rtweekend::{random_double, random_double_range, INFINITY};
 use crate::vec3::Vector;
 
 pub struct Triangle {
-    q: Vector,
-    u: Vector,
-    v: Vector,
-    w: Vector,
+    points: [Vector; 3],
     normal: Vector,
     mat: Arc<dyn Material>,
     bbox: AABB,
-    d: f64,
     area: f64,
+    normals: Option<[Vector; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
 }
 
 impl Triangle {
     pub fn new(a: Vector, b: Vector, c: Vector, d: Arc<dyn Material>) -> Self {
+        let n = (b - a).cross(&(c - a));
         let mut val: Triangle = Self {
-            q: a,
-            u: b - a,
-            v: c - a,
-            w: Default::default(),
-            normal: Default::default(),
+            points: [a, b, c],
+            normal: n.unit(),
             mat: d,
             bbox: Default::default(),
-            d: 0.0,
-            area: 0.0,
+            area: n.length() / 2.0,
+            normals: None,
+            uvs: None,
         };
-        let n = val.u.cross(&val.v);
-        val.normal = n.unit();
-        val.d = val.normal.dot(&a);
-        val.w = n / n.length_square();
         val.set_bounding_box();
-        val.area = n.length() / 2.0;
+        val
+    }
+    pub fn smooth_new(
+        points: [Vector; 3],
+        normals: Option<[Vector; 3]>,
+        uvs: Option<[(f64, f64); 3]>,
+        mat: Arc<dyn Material>,
+    ) -> Self {
+        let mut val = Self::new(points[0], points[1], points[2], mat);
+        val.normals = normals.map(|n| [n[0].unit(), n[1].unit(), n[2].unit()]);
+        val.uvs = uvs;
         val
     }
     pub fn set_bounding_box(&mut self) {
-        let bbox_diagonal1 = AABB::point_new(&self.q, &(self.q + self.u));
-        let bbox_diagonal2 = AABB::point_new(&self.q, &(self.q + self.v));
-        self.bbox = AABB::box_new(&bbox_diagonal1, &bbox_diagonal2);
-    }
-    fn is_interior(a: f64, b: f64) -> Option<HitRecord> {
-        let mut val: HitRecord = Default::default();
-        let unit_interval = Interval::new(0.0, 1.0);
-        if !unit_interval.contains(a)
-            || !unit_interval.contains(b)
-            || !unit_interval.contains(a + b)
-        {
-            return None;
-        }
-        val.u = a;
-        val.v = b;
-        Some(val)
+        let [a, b, c] = self.points;
+        self.bbox = AABB::box_new(&AABB::point_new(&a, &b), &AABB::point_new(&a, &c));
+    }
+}
+
+// Watertight ray/triangle test (Woop, Benthin and Wald 2013). The vertices are
+// moved into a space where the ray runs along +z from the origin, so the edge
+// functions only depend on each edge's own two endpoints: triangles sharing an
+// edge see exactly the same value with opposite sign and cannot both miss.
+// Returns `t` and the barycentric weights of the three vertices.
+pub fn intersect_watertight(
+    r: &Ray,
+    points: &[Vector; 3],
+    ray_t: &Interval,
+) -> Option<(f64, [f64; 3])> {
+    let d = r.direction;
+    let kz = (0..3)
+        .max_by(|&i, &j| {
+            d.at(i)
+                .abs()
+                .partial_cmp(&d.at(j).abs())
+                .unwrap_or(Ordering::Equal)
+        })
+        .unwrap();
+    let mut kx = (kz + 1) % 3;
+    let mut ky = (kx + 1) % 3;
+    if d.at(kz) < 0.0 {
+        std::mem::swap(&mut kx, &mut ky);
+    }
+    if d.at(kz) == 0.0 || d.at(kz).is_nan() {
+        return None;
+    }
+    let sx = d.at(kx) / d.at(kz);
+    let sy = d.at(ky) / d.at(kz);
+    let sz = 1.0 / d.at(kz);
+
+    let mut shear = [(0.0, 0.0, 0.0); 3];
+    for (s, p) in shear.iter_mut().zip(points.iter()) {
+        let rel = *p - r.origin;
+        *s = (
+            rel.at(kx) - sx * rel.at(kz),
+            rel.at(ky) - sy * rel.at(kz),
+            sz * rel.at(kz),
+        );
+    }
+    let [a, b, c] = shear;
+    let e0 = b.0 * c.1 - b.1 * c.0;
+    let e1 = c.0 * a.1 - c.1 * a.0;
+    let e2 = a.0 * b.1 - a.1 * b.0;
+    if (e0 < 0.0 || e1 < 0.0 || e2 < 0.0) && (e0 > 0.0 || e1 > 0.0 || e2 > 0.0) {
+        return None;
     }
+    let det = e0 + e1 + e2;
+    if det == 0.0 {
+        return None;
+    }
+    let t = (e0 * a.2 + e1 * b.2 + e2 * c.2) / det;
+    if !ray_t.contains(t) {
+        return None;
+    }
+    Some((t, [e0 / det, e1 / det, e2 / det]))
+}
+
+// Derivatives of the surface along u and v for a triangle whose corners carry
+// `uvs`, or None when those do not span the plane.
+pub fn uv_derivatives(points: &[Vector; 3], uvs: &[(f64, f64); 3]) -> Option<(Vector, Vector)> {
+    let (du1, dv1) = (uvs[1].0 - uvs[0].0, uvs[1].1 - uvs[0].1);
+    let (du2, dv2) = (uvs[2].0 - uvs[0].0, uvs[2].1 - uvs[0].1);
+    let det = du1 * dv2 - du2 * dv1;
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let edge1 = points[1] - points[0];
+    let edge2 = points[2] - points[0];
+    Some((
+        (edge1 * dv2 - edge2 * dv1) / det,
+        (edge2 * du1 - edge1 * du2) / det,
+    ))
 }
 
 impl Hittable for Triangle {
     fn hit(&self, r: &Ray, ray_t: &Interval) -> Option<HitRecord> {
-        let denom = self.normal.dot(&r.direction);
-        if denom.abs() < 1e-8 {
-            return None;
-        }
-        let t = (self.d - self.normal.dot(&r.origin)) / denom;
-        if !ray_t.contains(t) {
-            return None;
+        let (t, weights) = intersect_watertight(r, &self.points, ray_t)?;
+        let mut val = HitRecord {
+            t,
+            p: r.at(t),
+            mat: Some(self.mat.clone()),
+            u: weights[1],
+            v: weights[2],
+            ..Default::default()
+        };
+        val.set_face_normal(r, &self.normal);
+        if let Some(n) = self.normals {
+            let shading_normal = (n[0] * weights[0] + n[1] * weights[1] + n[2] * weights[2]).unit();
+            val.normal = if val.front_face {
+                shading_normal
+            } else {
+                shading_normal * -1.0
+            };
         }
-        let intersection = r.at(t);
-        let planar_hitpt_vector = intersection - self.q;
-        let alpha = self.w.dot(&planar_hitpt_vector.cross(&self.v));
-        let beta = self.w.dot(&self.u.cross(&planar_hitpt_vector));
-        if let Some(mut val) = Self::is_interior(alpha, beta) {
-            val.t = t;
-            val.p = intersection;
-            val.mat = Some(self.mat.clone());
-            val.set_face_normal(r, &self.normal);
-            Some(val)
-        } else {
-            None
+        // Without UVs, u and v are the barycentric weights of the second and
+        // third corner, which change along the edges towards them.
+        let [p0, p1, p2] = self.points;
+        (val.dpdu, val.dpdv) = (p1 - p0, p2 - p0);
+        if let Some(uv) = self.uvs {
+            val.u = uv[0].0 * weights[0] + uv[1].0 * weights[1] + uv[2].0 * weights[2];
+            val.v = uv[0].1 * weights[0] + uv[1].1 * weights[1] + uv[2].1 * weights[2];
+            if let Some(derivatives) = uv_derivatives(&self.points, &uv) {
+                (val.dpdu, val.dpdv) = derivatives;
+            }
         }
+        Some(val)
     }
     fn bounding_box(&self) -> AABB {
         self.bbox
@@ -95,16 +169,181 @@ impl Hittable for Triangle {
             &Interval::new(0.001, INFINITY),
         ) {
             let distance_squared = rec.t * rec.t * direction.length_square();
-            let cosine = (direction.dot(&rec.normal) / direction.length()).abs();
+            // The light's area is measured on the flat face, whatever normals
+            // its corners carry for shading.
+            let cosine = (direction.dot(&self.normal) / direction.length()).abs();
             distance_squared / (cosine * self.area)
         } else {
             0.0
         }
     }
     fn random(&self, origin: Vector) -> Vector {
+        let [p0, p1, p2] = self.points;
         let a = random_double();
         let b = random_double_range(0.0, 1.0 - a);
-        let p = self.q + self.u * a + self.v * b;
+        let p = p0 + (p1 - p0) * a + (p2 - p0) * b;
         p - origin
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable_list::HittableList;
+    use crate::material::Lambertian;
+    use crate::mesh::TriangleMesh;
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5)))
+    }
+
+    fn any_hit(triangles: &[Triangle], r: &Ray) -> bool {
+        triangles
+            .iter()
+            .any(|tri| tri.hit(r, &Interval::new(0.0, INFINITY)).is_some())
+    }
+
+    #[test]
+    fn hit_reports_distance_and_barycentrics() {
+        let tri = Triangle::new(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+            material(),
+        );
+        let r = Ray::new(
+            Vector::new(0.25, 0.5, 2.0),
+            Vector::new(0.0, 0.0, -1.0),
+            0.0,
+        );
+        let rec = tri.hit(&r, &Interval::new(0.0, INFINITY)).unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-12);
+        assert!((rec.u - 0.25).abs() < 1e-12);
+        assert!((rec.v - 0.5).abs() < 1e-12);
+        let miss = Ray::new(
+            Vector::new(0.75, 0.5, 2.0),
+            Vector::new(0.0, 0.0, -1.0),
+            0.0,
+        );
+        assert!(tri.hit(&miss, &Interval::new(0.0, INFINITY)).is_none());
+    }
+
+    #[test]
+    fn rays_through_shared_edge_never_slip_through() {
+        let a = Vector::new(0.1, 0.2, 0.3);
+        let b = Vector::new(1.7, 0.13, 0.31);
+        let c = Vector::new(1.9, 1.3, 0.7);
+        let d = Vector::new(0.2, 1.1, 0.5);
+        let triangles = [
+            Triangle::new(a, b, c, material()),
+            Triangle::new(a, c, d, material()),
+        ];
+        let origins = [
+            Vector::new(0.3, 0.7, 5.0),
+            Vector::new(-3.1, 2.9, 1.7),
+            Vector::new(4.3, -1.3, -2.2),
+        ];
+        for origin in origins {
+            for i in 1..1000 {
+                let target = a + (c - a) * (i as f64 / 1000.0);
+                let r = Ray::new(origin, target - origin, 0.0);
+                assert!(any_hit(&triangles, &r), "ray to edge point {} leaked", i);
+            }
+        }
+    }
+
+    #[test]
+    fn rays_through_shared_vertex_never_slip_through() {
+        let center = Vector::new(0.3, -0.7, 1.1);
+        let ring: Vec<Vector> = (0..7)
+            .map(|k| {
+                let angle = k as f64 * 2.0 * std::f64::consts::PI / 7.0;
+                center + Vector::new(angle.cos(), angle.sin(), 0.1 * (k as f64).sin())
+            })
+            .collect();
+        let triangles: Vec<Triangle> = (0..7)
+            .map(|k| Triangle::new(center, ring[k], ring[(k + 1) % 7], material()))
+            .collect();
+        for i in 0..500 {
+            let angle = i as f64 * 0.37;
+            let origin = center + Vector::new(3.0 * angle.cos(), 3.0 * angle.sin(), 2.5);
+            let r = Ray::new(origin, center - origin, 0.0);
+            assert!(any_hit(&triangles, &r), "ray {} to shared vertex leaked", i);
+        }
+    }
+
+    #[test]
+    fn closed_mesh_does_not_leak() {
+        let positions = vec![
+            Vector::new(1.3, 0.0, 0.0),
+            Vector::new(-0.9, 0.0, 0.0),
+            Vector::new(0.0, 1.1, 0.0),
+            Vector::new(0.0, -1.7, 0.0),
+            Vector::new(0.0, 0.0, 0.7),
+            Vector::new(0.0, 0.0, -1.2),
+        ];
+        let mut indices = Vec::new();
+        for x in [0, 1] {
+            for y in [2, 3] {
+                for z in [4, 5] {
+                    indices.push([x, y, z]);
+                }
+            }
+        }
+        let mesh = Arc::new(TriangleMesh::new(
+            positions.clone(),
+            Vec::new(),
+            Vec::new(),
+            indices,
+            material(),
+        ));
+        let mut world = HittableList::new();
+        for tri in TriangleMesh::triangles(&mesh) {
+            world.add(tri);
+        }
+        let origin = Vector::new(0.1, -0.05, 0.02);
+        let mut targets = positions.clone();
+        for (i, p) in positions.iter().enumerate() {
+            for q in &positions[i + 1..] {
+                targets.push((*p + *q) * 0.5);
+            }
+        }
+        for target in targets {
+            let r = Ray::new(origin, target - origin, 0.0);
+            assert!(world.hit(&r, &Interval::new(0.0, INFINITY)).is_some());
+        }
+    }
+
+    #[test]
+    fn pdf_ignores_vertex_normals() {
+        let points = [
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+        ];
+        let flat = Triangle::new(points[0], points[1], points[2], material());
+        let tilted = Vector::new(1.0, 1.0, 1.0);
+        let smooth = Triangle::smooth_new(points, Some([tilted; 3]), None, material());
+        let origin = Vector::new(0.25, 0.25, 2.0);
+        let direction = Vector::new(0.0, 0.0, -1.0);
+        // Straight on, the solid angle of a small patch is its area over d^2.
+        let expected = 4.0 / 0.5;
+        assert!((flat.pdf_value(origin, direction) - expected).abs() < 1e-9);
+        assert!((smooth.pdf_value(origin, direction) - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn nan_direction_misses() {
+        let points = [
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+        ];
+        let r = Ray::new(
+            Vector::new(0.25, 0.25, 2.0),
+            Vector::new(f64::NAN, 0.0, -1.0),
+            0.0,
+        );
+        assert!(intersect_watertight(&r, &points, &Interval::new(0.0, INFINITY)).is_none());
+    }
+}